@@ -1,12 +1,16 @@
-use std::{error::Error, time::Duration};
+use std::{collections::HashMap, error::Error, time::Duration};
 
 use colored::Colorize;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{header::HeaderName, Client};
-use serde_json::json;
+use reqwest::{header::HeaderName, Client, Method, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
 use tokio::time::sleep;
 
-use crate::model::{CurrentlyPlayingContext, PlayingItem};
+use crate::model::{
+    AdditionalType, AudioFeatures, AudioFeaturesPayload, CurrentlyPlayingContext, FullArtist, FullArtists, FullTrack,
+    FullTracks, Id, ItemId, PlayingItem, PrivateUser, SubscriptionLevel,
+};
 
 use crate::{
     auth::refresh_token,
@@ -28,9 +32,7 @@ pub async fn alias(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub async fn play(config: Config) -> Result<(), Box<dyn Error>> {
-    println!("Resuming playback on default device.");
-
+pub async fn play(config: Config, item: Option<ItemId>) -> Result<(), Box<dyn Error>> {
     let base = "https://api.spotify.com/v1/me/player/play".to_string();
     
     let url = match &config.defaults.device {
@@ -38,22 +40,43 @@ pub async fn play(config: Config) -> Result<(), Box<dyn Error>> {
         None => base
     };
 
-    if let Some(playlist) = &config.defaults.playlist {
-        let http = Client::new();
-        let (key, val) = get_auth_header(&config)?;
-        let req = http
-            .put(&url)
-            .header(key, val)
-            .json(&json!({
-                  "context_uri": playlist,
+    let default = match item {
+        Some(_) => None,
+        None => config.default_playlist()?,
+    };
+    let body = match (&item, &default) {
+        (Some(item), _) if item.is_context() => {
+            println!("Playing {}.", item);
+            json!({ "context_uri": item.uri() })
+        }
+        (Some(item), _) => {
+            println!("Playing {}.", item);
+            json!({ "uris": [item.uri()] })
+        }
+        (None, Some(playlist)) => {
+            println!("Playing default playlist on default device.");
+            json!({
+                  "context_uri": playlist.uri(),
                   "offset": {
                     "position": 0
                   },
                   "position_ms": 0
-            }));
+            })
+        }
+        (None, None) => {
+            println!("Resuming playback on default device.");
+            json!({})
+        }
+    };
 
-        let resp = req.send().await?;
-    }
+    let http = Client::new();
+    let (key, val) = get_auth_header(&config)?;
+    let req = http
+        .put(&url)
+        .header(key, val)
+        .json(&body);
+
+    check_status(req.send().await?).await?;
 
     sleep(Duration::from_millis(DELAY)).await;
 
@@ -70,12 +93,16 @@ pub async fn pause(config: Config) -> Result<(), Box<dyn Error>> {
         .header(key, val)
         .json(&());
 
-    let resp = req.send().await?;
+    check_status(req.send().await?).await?;
 
     Ok(())
 }
 
-pub async fn skip(config: Config) -> Result<(), Box<dyn Error>> {
+pub async fn skip(config: Config, seconds: Option<u64>) -> Result<(), Box<dyn Error>> {
+    if let Some(seconds) = seconds {
+        return seek_by(&config, seconds as i64).await;
+    }
+
     println!("Skipping to next song.");
 
     let http = Client::new();
@@ -85,14 +112,18 @@ pub async fn skip(config: Config) -> Result<(), Box<dyn Error>> {
         .header(key, val)
         .json(&());
 
-    let resp = req.send().await?;
+    check_status(req.send().await?).await?;
 
     sleep(Duration::from_millis(DELAY)).await;
 
     current(config).await
 }
 
-pub async fn back(config: Config) -> Result<(), Box<dyn Error>> {
+pub async fn back(config: Config, seconds: Option<u64>) -> Result<(), Box<dyn Error>> {
+    if let Some(seconds) = seconds {
+        return seek_by(&config, -(seconds as i64)).await;
+    }
+
     println!("Skipping to previous song.");
 
     let http = Client::new();
@@ -102,26 +133,42 @@ pub async fn back(config: Config) -> Result<(), Box<dyn Error>> {
         .header(key, val)
         .json(&());
 
-    let resp = req.send().await?;
+    check_status(req.send().await?).await?;
 
     sleep(Duration::from_millis(DELAY)).await;
 
     current(config).await
 }
 
-pub async fn current(config: Config) -> Result<(), Box<dyn Error>> {
-    let http = Client::new();
+/// Jump `seconds` forward, or back if negative, within the current item.
+async fn seek_by(config: &Config, seconds: i64) -> Result<(), Box<dyn Error>> {
+    let playing = currently_playing(config).await?.ok_or("Nothing is playing.")?;
+    let duration = match &playing.item {
+        Some(PlayingItem::Track(track)) => track.duration,
+        Some(PlayingItem::Episode(episode)) => episode.duration,
+        None => return Err("Nothing is playing.".into()),
+    };
+    let progress = playing.progress.unwrap_or_default();
 
-    let (key, val) = get_auth_header(&config)?;
-    let req = http
-        .get("https://api.spotify.com/v1/me/player/currently-playing")
-        .header(key, val);
+    let delta = Duration::from_secs(seconds.unsigned_abs());
+    let position = if seconds < 0 {
+        progress.saturating_sub(delta)
+    } else {
+        (progress + delta).min(duration)
+    };
 
-    let resp = req.send().await?;
+    let url = format!(
+        "https://api.spotify.com/v1/me/player/seek?position_ms={}",
+        position.as_millis()
+    );
+    send(config, Method::PUT, &url, &json!({})).await?;
+    println!("Jumped to {} of {}.", length(position), length(duration));
 
-    let json: Option<CurrentlyPlayingContext> = resp.json().await?;
+    Ok(())
+}
 
-    if let Some(json) = json {
+pub async fn current(config: Config) -> Result<(), Box<dyn Error>> {
+    if let Some(json) = currently_playing(&config).await? {
         match json.item {
             Some(PlayingItem::Track(track)) => {
                 if let Some(artist) = track.artists.first() {
@@ -134,7 +181,19 @@ pub async fn current(config: Config) -> Result<(), Box<dyn Error>> {
                     println!("Currently playing {}.", track.name.green());
                 }
             }
-            Some(PlayingItem::Episode(episode)) => {}
+            Some(PlayingItem::Episode(episode)) => {
+                println!(
+                    "Currently playing {} from {}.",
+                    episode.name.green(),
+                    episode.show.name.green()
+                );
+                println!(
+                    "{} of {}, {} left.",
+                    length(json.progress.unwrap_or_default()),
+                    length(episode.duration),
+                    length(episode.duration.saturating_sub(json.progress.unwrap_or_default()))
+                );
+            }
             _ => {}
         }
     } else {
@@ -144,10 +203,165 @@ pub async fn current(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Fetch what is currently playing, or `None` if nothing is.
+pub(crate) async fn currently_playing(config: &Config) -> Result<Option<CurrentlyPlayingContext>, Box<dyn Error>> {
+    let http = Client::new();
+
+    let (key, val) = get_auth_header(config)?;
+    let types: Vec<String> = [AdditionalType::Track, AdditionalType::Episode]
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    let url = format!(
+        "https://api.spotify.com/v1/me/player/currently-playing?additional_types={}",
+        types.join(",")
+    );
+    let req = http.get(&url).header(key, val);
+
+    let resp = check_status(req.send().await?).await?;
+    if resp.status() == StatusCode::NO_CONTENT {
+        return Ok(None);
+    }
+
+    Ok(resp.json().await?)
+}
+
+pub async fn whoami(config: Config) -> Result<(), Box<dyn Error>> {
+    let user = get_user(&config).await?;
+
+    println!(
+        "Profile {} is logged in as {}.",
+        config.profile.bold(),
+        user.display_name.as_deref().unwrap_or(&user.id).green()
+    );
+    println!("{:>16}: {}", "ID", user.id);
+    if let Some(email) = &user.email {
+        println!("{:>16}: {}", "Email", email);
+    }
+    if let Some(country) = &user.country {
+        println!("{:>16}: {}", "Country", country.to_string());
+    }
+    match user.product {
+        Some(SubscriptionLevel::Premium) => println!("{:>16}: {}", "Product", "Premium".green()),
+        Some(SubscriptionLevel::Free) => println!("{:>16}: {}", "Product", "Free".yellow()),
+        None => {}
+    }
+    if let Some(explicit) = &user.explicit_content {
+        let filter = match (explicit.filter_enabled, explicit.filter_locked) {
+            (true, true) => "filtered (locked)",
+            (true, false) => "filtered",
+            (false, true) => "allowed (locked)",
+            (false, false) => "allowed",
+        };
+        println!("{:>16}: {}", "Explicit content", filter);
+    }
+
+    Ok(())
+}
+
+/// Fetch the profile of the logged in user.
+pub async fn get_user(config: &Config) -> Result<PrivateUser, Box<dyn Error>> {
+    get(config, "https://api.spotify.com/v1/me").await
+}
+
+/// Fetch full track objects, 50 at a time.
+pub(crate) async fn get_tracks(config: &Config, ids: &[String]) -> Result<Vec<FullTrack>, Box<dyn Error>> {
+    let mut tracks = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(50) {
+        let url = format!("https://api.spotify.com/v1/tracks?ids={}", chunk.join(","));
+        let page: FullTracks = get(config, &url).await?;
+        tracks.extend(page.tracks);
+    }
+    Ok(tracks)
+}
+
+/// Fetch full artist objects, 50 at a time.
+pub(crate) async fn get_artists(config: &Config, ids: &[String]) -> Result<Vec<FullArtist>, Box<dyn Error>> {
+    let mut artists = Vec::with_capacity(ids.len());
+    for chunk in ids.chunks(50) {
+        let url = format!("https://api.spotify.com/v1/artists?ids={}", chunk.join(","));
+        let page: FullArtists = get(config, &url).await?;
+        artists.extend(page.artists);
+    }
+    Ok(artists)
+}
+
+/// Audio features of the tracks with the given IDs, keyed by ID. Tracks
+/// Spotify has no features for are left out.
+pub(crate) async fn get_audio_features(
+    config: &Config,
+    ids: &[String],
+) -> Result<HashMap<String, AudioFeatures>, Box<dyn Error>> {
+    let mut features = HashMap::with_capacity(ids.len());
+    for chunk in ids.chunks(100) {
+        let url = format!("https://api.spotify.com/v1/audio-features?ids={}", chunk.join(","));
+        let payload: AudioFeaturesPayload = get(config, &url).await?;
+        features.extend(payload.audio_features.into_iter().flatten().map(|f| (f.id.clone(), f)));
+    }
+    Ok(features)
+}
+
 pub async fn save_to_playlist(config: Config) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// GET `url` and deserialize the JSON response, turning Spotify's error
+/// object into an error message.
+pub(crate) async fn get<T: DeserializeOwned>(config: &Config, url: &str) -> Result<T, Box<dyn Error>> {
+    let http = Client::new();
+    let (key, val) = get_auth_header(config)?;
+    let resp = http.get(url).header(key, val).send().await?;
+
+    Ok(check_status(resp).await?.json().await?)
+}
+
+/// Send a `method` request with a JSON `body` to `url`, turning Spotify's
+/// error object into an error message.
+pub(crate) async fn send(config: &Config, method: Method, url: &str, body: &Value) -> Result<Response, Box<dyn Error>> {
+    let http = Client::new();
+    let (key, val) = get_auth_header(config)?;
+    let resp = http.request(method, url).header(key, val).json(body).send().await?;
+
+    check_status(resp).await
+}
+
+/// Turn an unsuccessful response into an error using Spotify's error message.
+pub(crate) async fn check_status(resp: Response) -> Result<Response, Box<dyn Error>> {
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        error: ErrorObject,
+    }
+    #[derive(Deserialize)]
+    struct ErrorObject {
+        message: String,
+        reason: Option<String>,
+    }
+
+    let status = resp.status();
+    match resp.json::<ErrorBody>().await {
+        Ok(body) if body.error.reason.as_deref() == Some("PREMIUM_REQUIRED") => {
+            Err(format!("Controlling playback requires Spotify Premium ({})", status).into())
+        }
+        // Logins from before a command needed a scope lack it.
+        Ok(body) if is_missing_scope(&body.error.message) => Err(format!(
+            "{} ({}), run sp login again to grant SpotR access",
+            body.error.message, status
+        )
+        .into()),
+        Ok(body) => Err(format!("{} ({})", body.error.message, status).into()),
+        Err(_) => Err(format!("Spotify API returned {}", status).into()),
+    }
+}
+
+/// Whether Spotify refused a request because the token lacks a scope.
+fn is_missing_scope(message: &str) -> bool {
+    message == "Insufficient client scope" || message == "Permissions missing"
+}
+
 fn get_auth_header(config: &Config) -> Result<(HeaderName, HeaderValue), Box<dyn Error>> {
     if let Some(access_token) = &config.auth.access_token {
         Ok((
@@ -159,4 +373,45 @@ fn get_auth_header(config: &Config) -> Result<(HeaderName, HeaderValue), Box<dyn
     }
 }
 
+mod browse;
+mod cleanup;
+mod export;
+mod features;
+mod history;
+mod import;
+mod library;
+mod mix;
 mod models;
+mod order;
+mod paginate;
+mod playlist;
+mod podcast;
+mod radio;
+mod recommend;
+mod search;
+mod smart;
+mod snapshot;
+mod stats;
+mod top;
+mod visualize;
+
+pub use browse::*;
+pub use cleanup::*;
+pub use export::*;
+pub use features::*;
+pub use history::*;
+pub use import::*;
+pub use library::*;
+pub use mix::*;
+pub use order::*;
+pub use paginate::*;
+pub use playlist::*;
+pub use podcast::*;
+pub use radio::*;
+pub use recommend::*;
+pub use search::*;
+pub use smart::*;
+pub use snapshot::*;
+pub use stats::*;
+pub use top::*;
+pub use visualize::*;
@@ -0,0 +1,395 @@
+//! Browsing artists, albums and shows
+//!
+//! Every listed track, album, artist or episode gets a row number, which
+//! `--play` and `--queue` refer to.
+use std::{error::Error, str::FromStr, time::Duration};
+
+use colored::Colorize;
+use futures::TryStreamExt;
+use reqwest::Method;
+use serde_json::json;
+use url::Url;
+
+use super::{
+    get, paginate_concurrent, PAGE_CONCURRENCY,
+    recommend::{enqueue, with_device},
+    search::artist_names,
+    send,
+};
+use crate::config::Config;
+use crate::model::{
+    AlbumId, AlbumType, ArtistId, CopyrightType, FullAlbum, FullArtist, FullArtists, FullShow, FullTracks, Id,
+    ItemId, Page, ResumePoint, SearchResult, SearchType, ShowId, SimplifiedAlbum, SimplifiedEpisode,
+    SimplifiedTrack,
+};
+
+/// Order and headings of the album groups on an artist's page.
+const GROUPS: [(AlbumType, &str); 4] = [
+    (AlbumType::Album, "Albums"),
+    (AlbumType::Single, "Singles and EPs"),
+    (AlbumType::Compilation, "Compilations"),
+    (AlbumType::AppearsOn, "Appears on"),
+];
+
+/// What to do with a row besides listing everything.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BrowseAction {
+    List,
+    Play(usize),
+    Queue(usize),
+}
+
+/// Something listed that can be played or queued.
+struct Row {
+    uri: String,
+    /// Album or show to keep playing after the row.
+    context: Option<String>,
+    /// Where to start, for partly played episodes.
+    position: Option<Duration>,
+}
+
+/// Collects rows while printing them numbered.
+#[derive(Default)]
+struct Rows(Vec<Row>);
+
+impl Rows {
+    fn push(&mut self, line: String, uri: String, context: Option<&str>, position: Option<Duration>) {
+        self.0.push(Row {
+            uri,
+            context: context.map(String::from),
+            position,
+        });
+        println!("{:>4}. {}", self.0.len(), line);
+    }
+}
+
+/// `m:ss`, or `h:mm:ss` from an hour on.
+pub fn length(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// How far into an episode of `duration` its resume point is, empty if it
+/// hasn't been started.
+pub fn progress(resume: Option<&ResumePoint>, duration: Duration) -> String {
+    match resume {
+        Some(resume) if resume.fully_played => "played".to_string(),
+        Some(resume) if resume.resume_position > Duration::default() => {
+            format!("{} left", length(duration.saturating_sub(resume.resume_position)))
+        }
+        _ => String::new(),
+    }
+}
+
+/// `albums` grouped by album type in the order of `GROUPS`, empty groups left
+/// out.
+pub fn group_albums(albums: Vec<SimplifiedAlbum>) -> Vec<(AlbumType, Vec<SimplifiedAlbum>)> {
+    let group_of = |album: &SimplifiedAlbum| {
+        album
+            .album_group
+            .as_deref()
+            .or(album.album_type.as_deref())
+            .and_then(|group| AlbumType::from_str(group).ok())
+            .unwrap_or(AlbumType::Album)
+    };
+
+    let mut groups: Vec<(AlbumType, Vec<SimplifiedAlbum>)> =
+        GROUPS.iter().map(|(group, _)| (*group, Vec::new())).collect();
+    for album in albums {
+        let group = group_of(&album);
+        if let Some((_, members)) = groups.iter_mut().find(|(kind, _)| *kind == group) {
+            members.push(album);
+        }
+    }
+    groups.retain(|(_, members)| !members.is_empty());
+    groups
+}
+
+fn heading(title: &str) {
+    println!("{}", title.bold());
+}
+
+fn year(date: Option<&str>) -> &str {
+    date.and_then(|date| date.get(..4)).unwrap_or("")
+}
+
+/// Show `query`, an artist URI or name: followers, genres, top tracks,
+/// albums and related artists.
+pub async fn artist(config: Config, query: String, action: BrowseAction) -> Result<(), Box<dyn Error>> {
+    let id = resolve::<ArtistId>(&config, &query, SearchType::Artist).await?;
+    let base = format!("https://api.spotify.com/v1/artists/{}", id);
+
+    let artist: FullArtist = get(&config, &base).await?;
+    let top: FullTracks = get(&config, &with_market(&config, format!("{}/top-tracks", base))).await?;
+    let url = with_market(
+        &config,
+        format!("{}/albums?include_groups=album,single,compilation,appears_on&limit=50", base),
+    );
+    let albums: Vec<SimplifiedAlbum> =
+        paginate_concurrent::<Page<SimplifiedAlbum>>(config.clone(), &url, PAGE_CONCURRENCY)
+            .try_collect()
+            .await?;
+    let related: FullArtists = get(&config, &format!("{}/related-artists", base)).await?;
+
+    println!("{} {}", artist.name.green().bold(), artist.uri.dimmed());
+    println!(
+        "{} followers, popularity {}",
+        artist.followers.total.to_string().bold(),
+        artist.popularity
+    );
+    if !artist.genres.is_empty() {
+        println!("{}", artist.genres.join(", ").dimmed());
+    }
+
+    let mut rows = Rows::default();
+    heading("Top tracks");
+    for track in top.tracks {
+        let line = format!("{} {} {}", track.name.green(), length(track.duration), track.album.name.dimmed());
+        rows.push(line, track.uri, None, None);
+    }
+    for (group, albums) in group_albums(albums) {
+        let title = GROUPS.iter().find(|(kind, _)| *kind == group).map_or("", |(_, title)| title);
+        heading(title);
+        for album in albums {
+            let line = format!(
+                "{} {} {}",
+                album.name.green(),
+                year(album.release_date.as_deref()),
+                artist_names(&album.artists).dimmed()
+            );
+            rows.push(line, album.uri.unwrap_or_default(), None, None);
+        }
+    }
+    heading("Related artists");
+    for related in related.artists {
+        let line = format!("{} {}", related.name.green(), related.genres.join(", ").dimmed());
+        rows.push(line, related.uri, None, None);
+    }
+
+    act(&config, &rows, action).await
+}
+
+/// Show `query`, an album URI or name, with its tracks and copyrights.
+pub async fn album(config: Config, query: String, action: BrowseAction) -> Result<(), Box<dyn Error>> {
+    let id = resolve::<AlbumId>(&config, &query, SearchType::Album).await?;
+    let album: FullAlbum = get(&config, &format!("https://api.spotify.com/v1/albums/{}", id)).await?;
+    let url = with_market(&config, format!("https://api.spotify.com/v1/albums/{}/tracks?limit=50", id));
+    let tracks: Vec<SimplifiedTrack> =
+        paginate_concurrent::<Page<SimplifiedTrack>>(config.clone(), &url, PAGE_CONCURRENCY)
+            .try_collect()
+            .await?;
+
+    let total = tracks.iter().map(|track| track.duration).sum();
+    println!(
+        "{} {} {}",
+        album.name.green().bold(),
+        artist_names(&album.artists),
+        album.uri.dimmed()
+    );
+    println!(
+        "{}, {}, {} tracks, {}",
+        album.album_type.to_string().replace('_', " "),
+        album.release_date,
+        tracks.len(),
+        length(total)
+    );
+
+    let mut rows = Rows::default();
+    let discs = tracks.iter().map(|track| track.disc_number).max().unwrap_or(1);
+    let mut disc = 0;
+    for track in tracks {
+        if discs > 1 && track.disc_number != disc {
+            disc = track.disc_number;
+            heading(&format!("Disc {}", disc));
+        }
+        let mut line = format!("{} {}", track.name.green(), length(track.duration));
+        // Only mention artists besides the album's.
+        if track.artists != album.artists {
+            line = format!("{} {}", line, artist_names(&track.artists).dimmed());
+        }
+        rows.push(line, track.uri, Some(&album.uri), None);
+    }
+
+    for copyright in &album.copyrights {
+        let symbol = match copyright._type {
+            CopyrightType::Copyright => "©",
+            CopyrightType::Performance => "℗",
+        };
+        if copyright.text.starts_with(symbol) {
+            println!("{}", copyright.text.dimmed());
+        } else {
+            println!("{}", format!("{} {}", symbol, copyright.text).dimmed());
+        }
+    }
+
+    act(&config, &rows, action).await
+}
+
+/// Show `query`, a show URI or name, with its latest `limit` episodes and how
+/// far into them you are.
+pub async fn show(config: Config, query: String, limit: usize, action: BrowseAction) -> Result<(), Box<dyn Error>> {
+    let id = resolve::<ShowId>(&config, &query, SearchType::Show).await?;
+    let show: FullShow = get(&config, &format!("https://api.spotify.com/v1/shows/{}?market=from_token", id)).await?;
+
+    let mut episodes = show.episodes.items;
+    let mut next = show.episodes.next;
+    while episodes.len() < limit {
+        let url = match next {
+            Some(url) => url,
+            None => break,
+        };
+        let page: Page<SimplifiedEpisode> = get(&config, &url).await?;
+        episodes.extend(page.items);
+        next = page.next;
+    }
+    episodes.truncate(limit);
+
+    println!("{} {} {}", show.name.green().bold(), show.publisher, show.uri.dimmed());
+    println!("{} episodes", show.episodes.total);
+
+    let mut rows = Rows::default();
+    for episode in episodes {
+        let resume = episode.resume_point.as_ref();
+        let line = format!(
+            "{} {} {} {}",
+            episode.release_date.dimmed(),
+            episode.name.green(),
+            length(episode.duration),
+            progress(resume, episode.duration).yellow()
+        );
+        // Start where you left off, unless the episode was finished.
+        let position = resume
+            .filter(|resume| !resume.fully_played)
+            .map(|resume| resume.resume_position);
+        rows.push(line, episode.uri, Some(&show.uri), position);
+    }
+
+    act(&config, &rows, action).await
+}
+
+/// ID of `query` if it's a URI, URL or ID, otherwise of the first `kind`
+/// found searching for it.
+async fn resolve<T: Id + FromStr>(config: &Config, query: &str, kind: SearchType) -> Result<String, Box<dyn Error>> {
+    if let Ok(id) = query.parse::<T>() {
+        return Ok(id.id().to_string());
+    }
+
+    let url = Url::parse_with_params(
+        "https://api.spotify.com/v1/search",
+        &[("q", query), ("type", &kind.to_string()), ("limit", "1")],
+    )?;
+    let id = match get(config, url.as_str()).await? {
+        SearchResult::Artists(page) => page.items.into_iter().next().map(|artist| artist.id),
+        SearchResult::Albums(page) => page.items.into_iter().next().and_then(|album| album.id),
+        SearchResult::Shows(page) => page.items.into_iter().next().map(|show| show.id),
+        _ => None,
+    };
+    id.ok_or_else(|| format!("No {} found for \"{}\".", kind.to_string(), query).into())
+}
+
+/// `url` restricted to the user's market. Only user tokens have a market,
+/// so catalog-only lookups go without.
+fn with_market(config: &Config, url: String) -> String {
+    match &config.auth.refresh_token {
+        Some(_) if url.contains('?') => format!("{}&market=from_token", url),
+        Some(_) => format!("{}?market=from_token", url),
+        None => url,
+    }
+}
+
+/// Play or queue row `action` refers to.
+async fn act(config: &Config, rows: &Rows, action: BrowseAction) -> Result<(), Box<dyn Error>> {
+    let number = match action {
+        BrowseAction::List => return Ok(()),
+        BrowseAction::Play(number) | BrowseAction::Queue(number) => number,
+    };
+    let row = number
+        .checked_sub(1)
+        .and_then(|index| rows.0.get(index))
+        .ok_or_else(|| format!("There is no row {}, pick one from 1 to {}.", number, rows.0.len()))?;
+    let item: ItemId = row.uri.parse()?;
+
+    if let BrowseAction::Queue(_) = action {
+        if item.is_context() {
+            return Err(format!("Only tracks and episodes can be queued, not {}.", item).into());
+        }
+        enqueue(config, &row.uri).await?;
+        println!("Queued {}.", item);
+        return Ok(());
+    }
+
+    let mut body = match &row.context {
+        _ if item.is_context() => json!({ "context_uri": row.uri }),
+        Some(context) => json!({ "context_uri": context, "offset": { "uri": row.uri } }),
+        None => json!({ "uris": [row.uri] }),
+    };
+    if let Some(position) = row.position {
+        body["position_ms"] = json!(position.as_millis() as u64);
+    }
+    let url = with_device(config, "https://api.spotify.com/v1/me/player/play".to_string());
+    send(config, Method::PUT, &url, &body).await?;
+    println!("Playing {}.", item);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_length() {
+        assert_eq!(length(Duration::from_secs(238)), "3:58");
+        assert_eq!(length(Duration::from_secs(3725)), "1:02:05");
+
+        let resume = |fully_played, seconds| ResumePoint {
+            fully_played,
+            resume_position: Duration::from_secs(seconds),
+        };
+        let duration = Duration::from_secs(600);
+        assert_eq!(progress(Some(&resume(false, 450)), duration), "2:30 left");
+        assert_eq!(progress(Some(&resume(true, 0)), duration), "played");
+        assert_eq!(progress(Some(&resume(false, 0)), duration), "");
+        assert_eq!(progress(None, duration), "");
+    }
+
+    #[test]
+    fn test_group_albums() {
+        let album = |name: &str, group: &str| {
+            serde_json::from_value::<SimplifiedAlbum>(json!({
+                "album_group": group,
+                "album_type": "album",
+                "artists": [],
+                "external_urls": {},
+                "href": null,
+                "id": null,
+                "images": [],
+                "name": name,
+                "type": "album",
+                "uri": null,
+            }))
+            .unwrap()
+        };
+        let groups = group_albums(vec![
+            album("Feature", "appears_on"),
+            album("First", "album"),
+            album("Single", "single"),
+            album("Second", "album"),
+        ]);
+        let names: Vec<(AlbumType, Vec<&str>)> = groups
+            .iter()
+            .map(|(group, albums)| (*group, albums.iter().map(|album| album.name.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                (AlbumType::Album, vec!["First", "Second"]),
+                (AlbumType::Single, vec!["Single"]),
+                (AlbumType::AppearsOn, vec!["Feature"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,316 @@
+//! Removing duplicate and unavailable tracks from playlists
+//!
+//! Items are removed by position against the snapshot they were read from,
+//! so edits made in the meantime by collaborators are never removed by
+//! mistake.
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    error::Error,
+};
+
+use colored::Colorize;
+use futures::TryStreamExt;
+use reqwest::Method;
+use serde_json::json;
+use strum::{Display, EnumString};
+
+use super::{
+    import::normalize,
+    paginate_concurrent,
+    PAGE_CONCURRENCY,
+    playlist::{fetch_details, mutate, playlist_url, print_snapshot},
+};
+use crate::config::Config;
+use crate::model::{Page, PlaylistId, PlaylistItem};
+
+/// Most items Spotify accepts in one remove request.
+const CHUNK_SIZE: usize = 100;
+
+/// Tracks further apart than this never match by title.
+const DURATION_TOLERANCE_MS: u64 = 3000;
+
+/// Words in a title marking a different recording rather than a reissue.
+const VERSIONS: &[&str] = &["live", "acoustic", "remix", "demo", "instrumental", "unplugged"];
+
+/// How duplicates are recognized: `uri`, `isrc` or `title` (normalized title,
+/// artist and duration).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum MatchStrategy {
+    Uri,
+    Isrc,
+    Title,
+}
+
+/// A playlist item as far as cleaning up is concerned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub position: usize,
+    /// URI of the item in the playlist. For relinked tracks this is the
+    /// original track rather than the one that plays.
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub isrc: Option<String>,
+    pub duration_ms: u64,
+    /// Why the track can't be played, if it can't.
+    pub unavailable: Option<String>,
+}
+
+/// An item to remove and why.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Removal {
+    pub position: usize,
+    pub uri: String,
+    pub name: String,
+    pub reason: String,
+}
+
+impl Removal {
+    fn new(entry: &Entry, reason: String) -> Self {
+        Removal {
+            position: entry.position,
+            uri: entry.uri.clone(),
+            name: format!("{} {}", entry.name, entry.artists.join(", ")),
+            reason,
+        }
+    }
+}
+
+/// Normalized `title` that still tells versions apart, so "Song (Live)" doesn't
+/// match "Song" while "Song - Remastered 2011" does.
+fn title_key(title: &str) -> String {
+    let lower = title.to_lowercase();
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).collect();
+    let mut key = normalize(title);
+    for version in VERSIONS.iter().filter(|version| words.contains(version)) {
+        key.push_str(" +");
+        key.push_str(version);
+    }
+    key
+}
+
+/// Later occurrences of tracks matching an earlier one by any of
+/// `strategies`. The first occurrence is always kept.
+pub fn find_duplicates(entries: &[Entry], strategies: &[MatchStrategy]) -> Vec<Removal> {
+    let mut uris: HashMap<&str, usize> = HashMap::new();
+    let mut isrcs: HashMap<&str, usize> = HashMap::new();
+    let mut titles: HashMap<(String, String), Vec<(u64, usize)>> = HashMap::new();
+    let mut removals = Vec::new();
+
+    for entry in entries {
+        let title_key = (
+            title_key(&entry.name),
+            entry.artists.first().map(|artist| normalize(artist)).unwrap_or_default(),
+        );
+
+        let duplicate = strategies.iter().find_map(|strategy| match strategy {
+            MatchStrategy::Uri => uris.get(entry.uri.as_str()).map(|kept| (*kept, "same URI")),
+            MatchStrategy::Isrc => entry
+                .isrc
+                .as_deref()
+                .and_then(|isrc| isrcs.get(isrc))
+                .map(|kept| (*kept, "same ISRC")),
+            MatchStrategy::Title => titles.get(&title_key).and_then(|kept| {
+                kept.iter()
+                    .find(|(duration, _)| duration.abs_diff(entry.duration_ms) <= DURATION_TOLERANCE_MS)
+                    .map(|(_, kept)| (*kept, "same title, artist and duration"))
+            }),
+        });
+
+        match duplicate {
+            Some((kept, reason)) => {
+                removals.push(Removal::new(entry, format!("{} as {}", reason, kept + 1)));
+            }
+            None => {
+                uris.entry(&entry.uri).or_insert(entry.position);
+                if let Some(isrc) = &entry.isrc {
+                    isrcs.entry(isrc).or_insert(entry.position);
+                }
+                titles
+                    .entry(title_key)
+                    .or_default()
+                    .push((entry.duration_ms, entry.position));
+            }
+        }
+    }
+
+    removals
+}
+
+/// Tracks that can't be played.
+pub fn find_unavailable(entries: &[Entry]) -> Vec<Removal> {
+    entries
+        .iter()
+        .filter_map(|entry| {
+            let reason = entry.unavailable.as_ref()?;
+            Some(Removal::new(entry, reason.clone()))
+        })
+        .collect()
+}
+
+/// Remove duplicate tracks from `playlist`.
+pub async fn dedupe_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    strategies: Vec<MatchStrategy>,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    let (snapshot, entries) = fetch_entries(&config, &playlist).await?;
+    let removals = find_duplicates(&entries, &strategies);
+    apply(&config, &playlist, snapshot, removals, "duplicates", dry_run).await
+}
+
+/// Remove tracks that are no longer playable from `playlist`.
+pub async fn prune_playlist(config: Config, playlist: PlaylistId, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let (snapshot, entries) = fetch_entries(&config, &playlist).await?;
+    let removals = find_unavailable(&entries);
+    apply(&config, &playlist, snapshot, removals, "unavailable tracks", dry_run).await
+}
+
+/// Report `removals` and, unless `dry_run`, remove them.
+async fn apply(
+    config: &Config,
+    playlist: &PlaylistId,
+    snapshot: String,
+    mut removals: Vec<Removal>,
+    what: &str,
+    dry_run: bool,
+) -> Result<(), Box<dyn Error>> {
+    if removals.is_empty() {
+        println!("No {} found.", what);
+        return Ok(());
+    }
+
+    for removal in &removals {
+        println!(
+            "{} {:>4}. {} {}",
+            "-".red(),
+            removal.position + 1,
+            removal.name,
+            format!("({})", removal.reason).dimmed()
+        );
+    }
+
+    if dry_run {
+        println!("Found {} {}, dry run so nothing was removed.", removals.len(), what);
+        return Ok(());
+    }
+
+    // Removing the last positions first keeps the earlier ones valid for the
+    // following requests.
+    removals.sort_by_key(|removal| Reverse(removal.position));
+    let url = playlist_url(playlist, "/tracks");
+    let mut snapshot = snapshot;
+
+    for chunk in removals.chunks(CHUNK_SIZE) {
+        let mut positions: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for removal in chunk {
+            positions.entry(&removal.uri).or_default().push(removal.position);
+        }
+        let tracks: Vec<_> = positions
+            .into_iter()
+            .map(|(uri, positions)| json!({ "uri": uri, "positions": positions }))
+            .collect();
+
+        let body = json!({ "tracks": tracks, "snapshot_id": snapshot });
+        snapshot = mutate(config, Method::DELETE, &url, &body).await?;
+    }
+
+    println!("Removed {} {}.", removals.len(), what);
+    print_snapshot(Some(snapshot));
+    Ok(())
+}
+
+/// Fetch the items of `playlist` along with the snapshot they belong to.
+async fn fetch_entries(config: &Config, playlist: &PlaylistId) -> Result<(String, Vec<Entry>), Box<dyn Error>> {
+    let details = fetch_details(config, playlist).await?;
+    // Asking for a market makes Spotify report playability and relink tracks.
+    let url = playlist_url(playlist, "/tracks?limit=100&market=from_token");
+    let items: Vec<PlaylistItem> = paginate_concurrent::<Page<PlaylistItem>>(config.clone(), &url, PAGE_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    let entries = items
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, item)| {
+            let track = item.track?;
+            let unavailable = match (&track.restrictions, track.is_playable) {
+                _ if track.is_local => None,
+                (Some(restriction), _) => Some(format!("restricted: {}", restriction.reason.to_string())),
+                (None, Some(false)) => Some("unavailable".to_string()),
+                _ => None,
+            };
+
+            Some(Entry {
+                position,
+                uri: track.linked_from.map_or(track.uri, |link| link.uri),
+                name: track.name,
+                artists: track.artists.into_iter().map(|artist| artist.name).collect(),
+                isrc: track.external_ids.get("isrc").cloned(),
+                duration_ms: track.duration.as_millis() as u64,
+                unavailable,
+            })
+        })
+        .collect();
+
+    Ok((details.snapshot_id, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(position: usize, uri: &str, name: &str, isrc: Option<&str>, duration_ms: u64) -> Entry {
+        Entry {
+            position,
+            uri: uri.to_string(),
+            name: name.to_string(),
+            artists: vec!["Queen".to_string()],
+            isrc: isrc.map(String::from),
+            duration_ms,
+            unavailable: None,
+        }
+    }
+
+    fn entries() -> Vec<Entry> {
+        vec![
+            entry(0, "spotify:track:a", "Under Pressure", Some("GBUM71029604"), 248_000),
+            entry(1, "spotify:track:a", "Under Pressure", Some("GBUM71029604"), 248_000),
+            entry(2, "spotify:track:b", "Under Pressure - Remastered 2011", Some("GBUM71029604"), 248_500),
+            entry(3, "spotify:track:c", "Under Pressure (Live)", None, 249_000),
+            entry(4, "spotify:track:d", "Under Pressure", None, 300_000),
+        ]
+    }
+
+    fn positions(removals: &[Removal]) -> Vec<usize> {
+        removals.iter().map(|removal| removal.position).collect()
+    }
+
+    #[test]
+    fn test_dedupe_strategies() {
+        let entries = entries();
+        assert_eq!(positions(&find_duplicates(&entries, &[MatchStrategy::Uri])), vec![1]);
+        assert_eq!(
+            positions(&find_duplicates(&entries, &[MatchStrategy::Uri, MatchStrategy::Isrc])),
+            vec![1, 2]
+        );
+        // Live versions are different recordings, not duplicates.
+        assert_eq!(positions(&find_duplicates(&entries, &[MatchStrategy::Title])), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_dedupe_reason() {
+        let removals = find_duplicates(&entries(), &[MatchStrategy::Isrc]);
+        assert_eq!(removals[0].reason, "same ISRC as 1");
+    }
+
+    #[test]
+    fn test_unavailable() {
+        let mut entries = entries();
+        entries[3].unavailable = Some("unavailable".to_string());
+        assert_eq!(positions(&find_unavailable(&entries)), vec![3]);
+    }
+}
@@ -0,0 +1,328 @@
+//! Exporting playlists, albums and liked songs to files
+use std::{error::Error, fs, path::PathBuf, str::FromStr};
+
+use chrono::Local;
+use colored::Colorize;
+use futures::{future, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use super::{
+    get, get_tracks, get_user, paginate, paginate_concurrent, PAGE_CONCURRENCY, playlist::{fetch_details, playlist_url}, search::artist_names,
+};
+use crate::config::Config;
+use crate::model::{
+    AlbumId, FullAlbum, FullTrack, Id, ItemId, Page, PlaylistId, PlaylistItem, SavedTrack,
+    SimplifiedPlaylist, SimplifiedTrack,
+};
+
+/// File format of an export: `m3u`, `xspf`, `csv` or `json`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum ExportFormat {
+    M3u,
+    Xspf,
+    Csv,
+    Json,
+}
+
+/// What to export: `liked`, or a playlist or album URI/URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportSource {
+    Liked,
+    Playlist(PlaylistId),
+    Album(AlbumId),
+}
+
+impl FromStr for ExportSource {
+    type Err = Box<dyn Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "liked" {
+            return Ok(ExportSource::Liked);
+        }
+
+        match value.parse::<ItemId>()? {
+            ItemId::Playlist(id) => Ok(ExportSource::Playlist(id)),
+            ItemId::Album(id) => Ok(ExportSource::Album(id)),
+            item => Err(format!("Can only export playlists, albums or liked, not {}", item).into()),
+        }
+    }
+}
+
+/// A track as written to an export.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportTrack {
+    pub title: String,
+    pub artists: String,
+    pub album: String,
+    pub duration_ms: u64,
+    pub isrc: Option<String>,
+    pub uri: String,
+}
+
+/// An exported playlist, album or library, the layout of JSON exports.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Export {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub tracks: Vec<ExportTrack>,
+}
+
+impl From<FullTrack> for ExportTrack {
+    fn from(track: FullTrack) -> Self {
+        ExportTrack {
+            artists: artist_names(&track.artists),
+            album: track.album.name,
+            duration_ms: track.duration.as_millis() as u64,
+            isrc: track.external_ids.get("isrc").cloned(),
+            title: track.name,
+            uri: track.uri,
+        }
+    }
+}
+
+impl Export {
+    /// Render the export in `format`.
+    pub fn render(&self, format: ExportFormat) -> Result<String, Box<dyn Error>> {
+        Ok(match format {
+            ExportFormat::M3u => self.to_m3u(),
+            ExportFormat::Xspf => self.to_xspf(),
+            ExportFormat::Csv => self.to_csv()?,
+            ExportFormat::Json => serde_json::to_string_pretty(self)?,
+        })
+    }
+
+    fn to_m3u(&self) -> String {
+        let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", self.name);
+        for track in &self.tracks {
+            out.push_str(&format!(
+                "#EXTINF:{},{} - {}\n{}\n",
+                track.duration_ms / 1000,
+                track.artists,
+                track.title,
+                track.uri
+            ));
+        }
+        out
+    }
+
+    fn to_xspf(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n");
+        out.push_str(&format!("  <title>{}</title>\n", xml_escape(&self.name)));
+        if !self.description.is_empty() {
+            out.push_str(&format!("  <annotation>{}</annotation>\n", xml_escape(&self.description)));
+        }
+        out.push_str("  <trackList>\n");
+        for track in &self.tracks {
+            out.push_str("    <track>\n");
+            out.push_str(&format!("      <location>{}</location>\n", xml_escape(&track.uri)));
+            if let Some(isrc) = &track.isrc {
+                out.push_str(&format!("      <identifier>isrc:{}</identifier>\n", xml_escape(isrc)));
+            }
+            out.push_str(&format!("      <title>{}</title>\n", xml_escape(&track.title)));
+            out.push_str(&format!("      <creator>{}</creator>\n", xml_escape(&track.artists)));
+            out.push_str(&format!("      <album>{}</album>\n", xml_escape(&track.album)));
+            out.push_str(&format!("      <duration>{}</duration>\n", track.duration_ms));
+            out.push_str("    </track>\n");
+        }
+        out.push_str("  </trackList>\n</playlist>\n");
+        out
+    }
+
+    fn to_csv(&self) -> Result<String, Box<dyn Error>> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for track in &self.tracks {
+            writer.serialize(track)?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Export `source` to `output`, or stdout if `None`.
+pub async fn export(
+    config: Config,
+    source: ExportSource,
+    format: ExportFormat,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let export = fetch_export(&config, &source).await?;
+    let rendered = export.render(format)?;
+
+    match output {
+        Some(path) => {
+            fs::write(&path, rendered)?;
+            eprintln!(
+                "Exported {} tracks from {} to {}.",
+                export.tracks.len(),
+                export.name.green(),
+                path.display()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
+
+/// Export every playlist the user owns into a directory, one file each.
+pub async fn export_all(config: Config, format: ExportFormat, output: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let dir = output.unwrap_or_else(|| PathBuf::from(format!("spotr-export-{}", Local::now().format("%Y-%m-%d"))));
+    fs::create_dir_all(&dir)?;
+
+    let user = get_user(&config).await?;
+    let playlists: Vec<SimplifiedPlaylist> = paginate::<Page<SimplifiedPlaylist>>(
+        config.clone(),
+        "https://api.spotify.com/v1/me/playlists?limit=50",
+    )
+    .try_filter(|playlist| future::ready(playlist.owner.id == user.id))
+    .try_collect()
+    .await?;
+
+    for playlist in &playlists {
+        let id: PlaylistId = playlist.id.parse()?;
+        let export = fetch_export(&config, &ExportSource::Playlist(id)).await?;
+        let path = dir.join(format!("{}-{}.{}", file_name(&playlist.name), playlist.id, format));
+        fs::write(&path, export.render(format)?)?;
+        println!("{:>5} tracks {}", export.tracks.len(), playlist.name.green());
+    }
+
+    println!("Exported {} playlists to {}.", playlists.len(), dir.display());
+    Ok(())
+}
+
+/// Fetch the name and tracks of `source`.
+pub(crate) async fn fetch_export(config: &Config, source: &ExportSource) -> Result<Export, Box<dyn Error>> {
+    match source {
+        ExportSource::Liked => {
+            let tracks: Vec<ExportTrack> = paginate_concurrent::<Page<SavedTrack>>(
+                config.clone(),
+                "https://api.spotify.com/v1/me/tracks?limit=50",
+                PAGE_CONCURRENCY,
+            )
+            .map_ok(|saved| ExportTrack::from(saved.track))
+            .try_collect()
+            .await?;
+
+            Ok(Export {
+                name: "Liked Songs".to_string(),
+                description: String::new(),
+                tracks,
+            })
+        }
+        ExportSource::Playlist(id) => {
+            let playlist = fetch_details(config, id).await?;
+            let url = playlist_url(id, "/tracks?limit=100");
+            let tracks: Vec<ExportTrack> =
+                paginate_concurrent::<Page<PlaylistItem>>(config.clone(), &url, PAGE_CONCURRENCY)
+                    .try_filter_map(|item| future::ready(Ok(item.track.map(ExportTrack::from))))
+                    .try_collect()
+                    .await?;
+
+            Ok(Export {
+                name: playlist.name,
+                description: playlist.description.unwrap_or_default(),
+                tracks,
+            })
+        }
+        ExportSource::Album(id) => {
+            let album: FullAlbum = get(config, &format!("https://api.spotify.com/v1/albums/{}", id.id())).await?;
+            let url = format!("https://api.spotify.com/v1/albums/{}/tracks?limit=50", id.id());
+            let ids: Vec<String> = paginate::<Page<SimplifiedTrack>>(config.clone(), &url)
+                .try_filter_map(|track| future::ready(Ok(track.id)))
+                .try_collect()
+                .await?;
+
+            // Album tracks lack the album and ISRC, so fetch the full tracks.
+            let tracks = get_tracks(config, &ids).await?.into_iter().map(ExportTrack::from).collect();
+
+            Ok(Export {
+                name: album.name,
+                description: String::new(),
+                tracks,
+            })
+        }
+    }
+}
+
+/// `name` with characters that aren't safe in file names replaced.
+fn file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || "-_ ".contains(c) { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> Export {
+        Export {
+            name: "Rock & Roll".to_string(),
+            description: String::new(),
+            tracks: vec![ExportTrack {
+                title: "Song <1>".to_string(),
+                artists: "A, B".to_string(),
+                album: "Album".to_string(),
+                duration_ms: 185_500,
+                isrc: Some("USRC17607839".to_string()),
+                uri: "spotify:track:6rqhFgbbKwnb9MLmUQDhG6".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_render_m3u() {
+        let m3u = export().render(ExportFormat::M3u).unwrap();
+        assert_eq!(
+            m3u,
+            "#EXTM3U\n#PLAYLIST:Rock & Roll\n#EXTINF:185,A, B - Song <1>\nspotify:track:6rqhFgbbKwnb9MLmUQDhG6\n"
+        );
+    }
+
+    #[test]
+    fn test_render_xspf_escapes() {
+        let xspf = export().render(ExportFormat::Xspf).unwrap();
+        assert!(xspf.contains("<title>Rock &amp; Roll</title>"));
+        assert!(xspf.contains("<title>Song &lt;1&gt;</title>"));
+        assert!(xspf.contains("<identifier>isrc:USRC17607839</identifier>"));
+    }
+
+    #[test]
+    fn test_render_csv_and_json() {
+        let csv = export().render(ExportFormat::Csv).unwrap();
+        assert_eq!(csv.lines().next(), Some("title,artists,album,duration_ms,isrc,uri"));
+        assert!(csv.contains("\"A, B\""));
+
+        let json = export().render(ExportFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Export>(&json).unwrap(), export());
+    }
+
+    #[test]
+    fn test_source() {
+        assert_eq!("liked".parse::<ExportSource>().unwrap(), ExportSource::Liked);
+        assert!(matches!(
+            "spotify:album:6akEvsycLGftJxYudPjmqK".parse::<ExportSource>(),
+            Ok(ExportSource::Album(_))
+        ));
+        assert!("spotify:track:6rqhFgbbKwnb9MLmUQDhG6".parse::<ExportSource>().is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Audio features and analysis of tracks
+use std::{convert::TryFrom, error::Error, fmt};
+
+use colored::Colorize;
+
+use super::{currently_playing, get, search::artist_names};
+use crate::config::Config;
+use crate::model::{AudioAnalysis, AudioFeatures, FullTrack, Id, Modality, PlayingItem, TrackId};
+
+pub(crate) const PITCH_CLASSES: [&str; 12] = ["C", "C♯", "D", "D♯", "E", "F", "F♯", "G", "G♯", "A", "A♯", "B"];
+
+/// Width of the bars drawn for 0–1 features.
+const BAR_WIDTH: usize = 20;
+
+/// A key in musical notation, e.g. `F♯ minor`. Spotify uses -1 when no key
+/// was detected.
+pub fn key_name(key: i32, mode: Modality) -> Option<String> {
+    let note = PITCH_CLASSES.get(usize::try_from(key).ok()?)?;
+    Some(match mode {
+        Modality::Major => format!("{} major", note),
+        Modality::Minor => format!("{} minor", note),
+        Modality::NoResult => note.to_string(),
+    })
+}
+
+/// A key's position on the Camelot wheel DJs use for harmonic mixing, e.g.
+/// `8B` for C major and `8A` for its relative minor, A minor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Camelot {
+    /// 1 to 12, going up a fifth with every step.
+    pub number: usize,
+    pub minor: bool,
+}
+
+impl Camelot {
+    pub fn new(key: i32, mode: Modality) -> Option<Self> {
+        let key = usize::try_from(key).ok().filter(|key| *key < 12)?;
+        // Going up a fifth (7 semitones) moves one step around the wheel.
+        let number = |major: usize| (major * 7 + 7) % 12 + 1;
+        match mode {
+            Modality::Major => Some(Camelot { number: number(key), minor: false }),
+            Modality::Minor => Some(Camelot { number: number((key + 3) % 12), minor: true }),
+            Modality::NoResult => None,
+        }
+    }
+
+    /// Steps around the wheel between two keys, switching between major and
+    /// minor counting as one. Keys at most one step apart mix well.
+    pub fn distance(self, other: Camelot) -> usize {
+        let steps = (self.number + 12 - other.number) % 12;
+        steps.min(12 - steps) + usize::from(self.minor != other.minor)
+    }
+}
+
+impl fmt::Display for Camelot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.number, if self.minor { 'A' } else { 'B' })
+    }
+}
+
+/// `value` between 0 and 1 drawn as a bar.
+pub fn bar(value: f32, width: usize) -> String {
+    let filled = ((value.clamp(0.0, 1.0) * width as f32).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+fn describe_key(key: i32, mode: Modality) -> String {
+    match (key_name(key, mode), Camelot::new(key, mode)) {
+        (Some(name), Some(code)) => format!("{} ({})", name, code),
+        (Some(name), None) => name,
+        _ => "unknown".to_string(),
+    }
+}
+
+/// `m:ss` of a position in seconds.
+fn timestamp(seconds: f32) -> String {
+    let seconds = seconds.max(0.0).round() as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Show the audio features of `track`, or of the current track.
+pub async fn features(config: Config, track: Option<TrackId>) -> Result<(), Box<dyn Error>> {
+    let track = track_or_current(&config, track).await?;
+    let id = track.id.as_deref().ok_or("Local files have no audio features.")?;
+    let features: AudioFeatures = get(&config, &format!("https://api.spotify.com/v1/audio-features/{}", id)).await?;
+
+    println!("{} {}", track.name.green().bold(), artist_names(&track.artists));
+    println!("{:>16}: {:.1} BPM", "Tempo", features.tempo);
+    println!("{:>16}: {}", "Key", describe_key(features.key, features.mode));
+    println!("{:>16}: {}/4", "Time signature", features.time_signature);
+    println!("{:>16}: {:.1} dB", "Loudness", features.loudness);
+
+    for (name, value) in &[
+        ("Danceability", features.danceability),
+        ("Energy", features.energy),
+        ("Valence", features.valence),
+        ("Acousticness", features.acousticness),
+        ("Instrumentalness", features.instrumentalness),
+        ("Liveness", features.liveness),
+        ("Speechiness", features.speechiness),
+    ] {
+        println!("{:>16}: {} {:.2}", name, bar(*value, BAR_WIDTH).green(), value);
+    }
+
+    Ok(())
+}
+
+/// Summarize the audio analysis of `track`, or of the current track, section
+/// by section.
+pub async fn analysis(config: Config, track: Option<TrackId>) -> Result<(), Box<dyn Error>> {
+    let track = track_or_current(&config, track).await?;
+    let id = track.id.as_deref().ok_or("Local files have no audio analysis.")?;
+    let analysis: AudioAnalysis = get(&config, &format!("https://api.spotify.com/v1/audio-analysis/{}", id)).await?;
+    let summary = &analysis.track;
+
+    println!("{} {}", track.name.green().bold(), artist_names(&track.artists));
+    println!(
+        "{} BPM, {}, {}/4, {:.1} dB, {} bars, {} beats",
+        format!("{:.1}", summary.tempo).bold(),
+        describe_key(summary.key as i32, summary.mode),
+        summary.time_signature,
+        summary.loudness,
+        analysis.bars.len(),
+        analysis.beats.len()
+    );
+    println!(
+        "{}",
+        format!(
+            "Fade in until {}, fade out from {}",
+            timestamp(summary.end_of_fade_in),
+            timestamp(summary.start_of_fade_out)
+        )
+        .dimmed()
+    );
+
+    let mut previous: Option<(f32, (i32, Modality), i32)> = None;
+    for section in &analysis.sections {
+        let tempo = format!("{:>6.1} BPM", section.tempo);
+        let key = format!("{:<20}", describe_key(section.key, section.mode));
+        let signature = format!("{}/4", section.time_signature);
+
+        // Highlight what changed since the previous section.
+        let (tempo, key, signature) = match previous {
+            Some((previous_tempo, previous_key, previous_signature)) => (
+                highlight(tempo, (section.tempo - previous_tempo).abs() >= 1.0),
+                highlight(key, (section.key, section.mode) != previous_key),
+                highlight(signature, section.time_signature != previous_signature),
+            ),
+            None => (tempo.normal(), key.normal(), signature.normal()),
+        };
+        println!(
+            "{:>5}–{:<5} {} {} {} {:>6.1} dB",
+            timestamp(section.time_interval.start),
+            timestamp(section.time_interval.start + section.time_interval.duration),
+            tempo,
+            key,
+            signature,
+            section.loudness
+        );
+
+        previous = Some((section.tempo, (section.key, section.mode), section.time_signature));
+    }
+
+    Ok(())
+}
+
+fn highlight(value: String, changed: bool) -> colored::ColoredString {
+    if changed {
+        value.yellow()
+    } else {
+        value.normal()
+    }
+}
+
+/// Fetch `track`, or the track currently playing if none is given.
+pub(crate) async fn track_or_current(config: &Config, track: Option<TrackId>) -> Result<FullTrack, Box<dyn Error>> {
+    if let Some(track) = track {
+        return get(config, &format!("https://api.spotify.com/v1/tracks/{}", track.id())).await;
+    }
+
+    match currently_playing(config).await?.and_then(|playing| playing.item) {
+        Some(PlayingItem::Track(track)) => Ok(track),
+        Some(PlayingItem::Episode(_)) => Err("Episodes have no audio features.".into()),
+        None => Err("Not currently playing, give a track URI.".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_name() {
+        assert_eq!(key_name(6, Modality::Minor).as_deref(), Some("F♯ minor"));
+        assert_eq!(key_name(0, Modality::Major).as_deref(), Some("C major"));
+        assert_eq!(key_name(-1, Modality::Major), None);
+    }
+
+    #[test]
+    fn test_camelot() {
+        let code = |key, mode| Camelot::new(key, mode).unwrap();
+        assert_eq!(code(0, Modality::Major).to_string(), "8B");
+        assert_eq!(code(9, Modality::Minor).to_string(), "8A");
+        assert_eq!(code(7, Modality::Major).to_string(), "9B");
+        assert_eq!(code(11, Modality::Major).to_string(), "1B");
+        assert_eq!(code(6, Modality::Minor).to_string(), "11A");
+        assert_eq!(code(5, Modality::Minor).to_string(), "4A");
+        assert_eq!(Camelot::new(-1, Modality::Minor), None);
+    }
+
+    #[test]
+    fn test_camelot_distance() {
+        let c_major = Camelot::new(0, Modality::Major).unwrap();
+        let distance = |key, mode| c_major.distance(Camelot::new(key, mode).unwrap());
+        assert_eq!(distance(0, Modality::Major), 0);
+        assert_eq!(distance(9, Modality::Minor), 1);
+        assert_eq!(distance(5, Modality::Major), 1);
+        assert_eq!(distance(7, Modality::Major), 1);
+        assert_eq!(distance(4, Modality::Minor), 2);
+        assert_eq!(distance(6, Modality::Major), 6);
+    }
+
+    #[test]
+    fn test_bar() {
+        assert_eq!(bar(0.5, 4), "██░░");
+        assert_eq!(bar(1.5, 2), "██");
+    }
+}
@@ -0,0 +1,181 @@
+//! Recently played tracks and the local listening log
+use std::error::Error;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use futures::TryStreamExt;
+
+use super::{get_artists, get_audio_features, paginate, search::artist_names};
+use crate::config::Config;
+use crate::history::{self, Play};
+use crate::model::{CursorBasedPage, PlayHistory};
+use crate::smart::Features;
+use crate::output::{self, OutputFormat, Record};
+
+/// Most plays Spotify returns.
+const RECENTLY_PLAYED: usize = 50;
+
+impl Record for Play {
+    const COLUMNS: &'static [&'static str] = &["played_at", "name", "artists", "album", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.played_at.to_rfc3339(),
+            self.name.clone(),
+            self.artists.clone(),
+            self.album.clone(),
+            self.uri.clone(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.played_at.format("%Y-%m-%d %H:%M").to_string().dimmed(),
+            self.name.green(),
+            self.artists,
+            self.uri.dimmed()
+        )
+    }
+}
+
+impl From<PlayHistory> for Play {
+    fn from(history: PlayHistory) -> Self {
+        Play {
+            played_at: history.played_at,
+            artists: artist_names(&history.track.artists),
+            artist_ids: history.track.artists.iter().filter_map(|artist| artist.id.clone()).collect(),
+            album: history.track.album.name,
+            duration_ms: history.track.duration.as_millis() as u64,
+            name: history.track.name,
+            uri: history.track.uri,
+            context: history.context.map(|context| context.uri),
+        }
+    }
+}
+
+/// Start of `date` in UTC.
+fn start_of(date: NaiveDate) -> DateTime<Utc> {
+    DateTime::from_utc(date.and_hms(0, 0, 0), Utc)
+}
+
+/// The last plays Spotify remembers, newest first.
+async fn recently_played(
+    config: &Config,
+    before: Option<NaiveDate>,
+    after: Option<NaiveDate>,
+) -> Result<Vec<Play>, Box<dyn Error>> {
+    let mut url = format!("https://api.spotify.com/v1/me/player/recently-played?limit={}", RECENTLY_PLAYED);
+    // Spotify takes one of the two cursors.
+    if let Some(before) = before {
+        url = format!("{}&before={}", url, start_of(before).timestamp_millis());
+    } else if let Some(after) = after {
+        url = format!("{}&after={}", url, start_of(after).timestamp_millis());
+    }
+
+    paginate::<CursorBasedPage<PlayHistory>>(config.clone(), &url)
+        .map_ok(Play::from)
+        .try_collect()
+        .await
+}
+
+/// Add the plays Spotify remembers to the local log, returning how many
+/// weren't logged yet.
+pub(crate) async fn sync_log(config: &Config) -> Result<usize, Box<dyn Error>> {
+    let plays = recently_played(config, None, None).await?;
+    log_plays(config, &plays).await
+}
+
+/// Append `plays` to the local log and store the genres and audio features
+/// of what they played, returning how many plays weren't logged yet.
+async fn log_plays(config: &Config, plays: &[Play]) -> Result<usize, Box<dyn Error>> {
+    let added = history::append(&config.profile, plays)?;
+    // Statistics work without them, so don't fail the whole sync.
+    if let Err(why) = store_metadata(config, plays).await {
+        eprintln!("Couldn't store genres and audio features: {}", why);
+    }
+    Ok(added)
+}
+
+/// Fetch genres and audio features not stored yet.
+async fn store_metadata(config: &Config, plays: &[Play]) -> Result<(), Box<dyn Error>> {
+    let mut metadata = history::load_metadata()?;
+
+    let mut artist_ids: Vec<String> = plays
+        .iter()
+        .flat_map(|play| play.artist_ids.iter())
+        .filter(|id| !metadata.artists.contains_key(*id))
+        .cloned()
+        .collect();
+    artist_ids.sort();
+    artist_ids.dedup();
+    let mut track_ids: Vec<String> = plays
+        .iter()
+        .filter_map(Play::track_id)
+        .filter(|id| !metadata.features.contains_key(*id))
+        .map(String::from)
+        .collect();
+    track_ids.sort();
+    track_ids.dedup();
+    if artist_ids.is_empty() && track_ids.is_empty() {
+        return Ok(());
+    }
+
+    for artist in get_artists(config, &artist_ids).await? {
+        let info = history::Artist {
+            name: artist.name,
+            genres: artist.genres,
+        };
+        metadata.artists.insert(artist.id, info);
+    }
+    for (id, features) in get_audio_features(config, &track_ids).await? {
+        metadata.features.insert(id, Features::from(&features));
+    }
+
+    history::save_metadata(&metadata)
+}
+
+/// List the last `limit` plays played before `before` and on or after
+/// `after`, from the local log if `local` is set. Plays fetched from Spotify
+/// are added to the log.
+pub async fn history(
+    config: Config,
+    limit: usize,
+    before: Option<NaiveDate>,
+    after: Option<NaiveDate>,
+    local: bool,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut plays = if local {
+        let mut plays = history::load(&config.profile)?;
+        plays.reverse();
+        plays
+    } else {
+        let plays = recently_played(&config, before, after).await?;
+        log_plays(&config, &plays).await?;
+        plays
+    };
+
+    plays.retain(|play| {
+        before.map_or(true, |before| play.played_at < start_of(before))
+            && after.map_or(true, |after| play.played_at >= start_of(after))
+    });
+    plays.truncate(limit);
+
+    output::print(format, &plays)
+}
+
+/// Add the plays Spotify remembers to the local log. Run it at least every
+/// 50 tracks, e.g. from cron, to keep the log complete.
+pub async fn history_sync(config: Config) -> Result<(), Box<dyn Error>> {
+    let added = sync_log(&config).await?;
+
+    let total = history::load(&config.profile)?.len();
+    println!(
+        "Logged {} new plays, {} in total in {}.",
+        added.to_string().green(),
+        total,
+        history::log_path(&config.profile)?.display()
+    );
+    Ok(())
+}
@@ -0,0 +1,475 @@
+//! Importing playlists from M3U, CSV and JSON files
+//!
+//! Each entry is resolved to a Spotify track by, in order: its Spotify URI,
+//! its ISRC, or fuzzy matching of title, artists and duration against a
+//! track search.
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use colored::Colorize;
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use strsim::normalized_levenshtein;
+use url::Url;
+
+use super::{
+    get,
+    PAGE_CONCURRENCY,
+    playlist::{add_uris, new_playlist, print_snapshot},
+    search::artist_names,
+};
+use crate::config::Config;
+use crate::model::{FullTrack, Id, PlaylistId, SearchTracks, TrackId};
+
+/// Candidates considered for fuzzy matching.
+const SEARCH_LIMIT: u32 = 10;
+
+/// Where imported tracks go: `new` or a playlist URI/URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportTarget {
+    New,
+    Playlist(PlaylistId),
+}
+
+impl FromStr for ImportTarget {
+    type Err = Box<dyn Error>;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "new" => Ok(ImportTarget::New),
+            _ => Ok(ImportTarget::Playlist(value.parse()?)),
+        }
+    }
+}
+
+/// An entry of an import file. Every field is optional since files from other
+/// services carry different information.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportEntry {
+    pub title: Option<String>,
+    pub artists: Option<String>,
+    pub album: Option<String>,
+    pub duration_ms: Option<u64>,
+    pub isrc: Option<String>,
+    pub uri: Option<String>,
+}
+
+/// How an entry was resolved.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Resolution {
+    Uri(TrackId),
+    Isrc(FullTrack),
+    /// Best fuzzy match and its confidence between 0 and 1.
+    Fuzzy(FullTrack, f64),
+    Unresolved(String),
+}
+
+#[derive(Deserialize)]
+struct JsonImport {
+    #[serde(default)]
+    name: Option<String>,
+    tracks: Vec<ImportEntry>,
+}
+
+/// Import `file` into `target`, only reporting matches if `dry_run`.
+pub async fn import(
+    config: Config,
+    file: PathBuf,
+    target: ImportTarget,
+    name: Option<String>,
+    min_confidence: f64,
+    dry_run: bool,
+    report: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let (file_name, entries) = read_entries(&file)?;
+
+    let resolutions: Vec<Resolution> = stream::iter(&entries)
+        .map(|entry| resolve(&config, entry, min_confidence))
+        .buffered(PAGE_CONCURRENCY)
+        .collect()
+        .await;
+
+    let mut uris = Vec::new();
+    let mut unresolved = Vec::new();
+    for (i, (entry, resolution)) in entries.iter().zip(&resolutions).enumerate() {
+        let (confidence, method, found, uri) = match resolution {
+            Resolution::Uri(id) => (1.0, "uri", id.to_string(), id.uri()),
+            Resolution::Isrc(track) => (1.0, "isrc", describe_track(track), track.uri.clone()),
+            Resolution::Fuzzy(track, confidence) => {
+                (*confidence, "fuzzy", describe_track(track), track.uri.clone())
+            }
+            Resolution::Unresolved(reason) => {
+                println!("{:>4} {:>4} {} {}", i + 1, "--".red(), describe_entry(entry), reason.red());
+                unresolved.push((entry.clone(), reason.clone()));
+                continue;
+            }
+        };
+
+        let percent = format!("{:.0}%", confidence * 100.0);
+        let percent = if confidence >= 0.9 { percent.green() } else { percent.yellow() };
+        println!(
+            "{:>4} {:>4} {} {} {} {}",
+            i + 1,
+            percent,
+            describe_entry(entry),
+            "->".dimmed(),
+            found,
+            format!("({})", method).dimmed()
+        );
+        uris.push(uri);
+    }
+
+    println!(
+        "Resolved {} of {} entries.",
+        uris.len().to_string().green(),
+        entries.len()
+    );
+
+    if !unresolved.is_empty() {
+        let report = report.unwrap_or_else(|| file.with_extension("unresolved.csv"));
+        write_report(&report, &unresolved)?;
+        println!("Unresolved entries written to {}.", report.display());
+    }
+
+    if dry_run {
+        println!("Dry run, nothing was imported.");
+        return Ok(());
+    }
+
+    let playlist = match target {
+        ImportTarget::Playlist(playlist) => playlist,
+        ImportTarget::New => {
+            let name = name.or(file_name).unwrap_or_else(|| "Imported playlist".to_string());
+            let created = new_playlist(&config, &name, "Imported by SpotR", false, false).await?;
+            println!("Created {} {}", created.name.green(), created.uri.dimmed());
+            created.uri.parse()?
+        }
+    };
+
+    let snapshot = add_uris(&config, &playlist, &uris, None).await?;
+    println!("Added {} tracks to {}.", uris.len(), playlist);
+    print_snapshot(snapshot);
+    Ok(())
+}
+
+/// Read the entries of `path`, picking the parser by extension. Also returns
+/// the playlist name if the file has one.
+pub fn read_entries(path: &Path) -> Result<(Option<String>, Vec<ImportEntry>), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|why| format!("Cannot read {}: {}", path.display(), why))?;
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "json" => {
+            let import: JsonImport = serde_json::from_str(&contents)?;
+            Ok((import.name.or(stem), import.tracks))
+        }
+        "csv" => {
+            let mut reader = csv::Reader::from_reader(contents.as_bytes());
+            let entries = reader.deserialize().collect::<Result<Vec<ImportEntry>, _>>()?;
+            Ok((stem, entries))
+        }
+        "m3u" | "m3u8" => {
+            let (name, entries) = parse_m3u(&contents);
+            Ok((name.or(stem), entries))
+        }
+        _ => Err(format!("Don't know how to import {}, expected .m3u, .csv or .json", path.display()).into()),
+    }
+}
+
+/// Parse an (extended) M3U playlist. Entries are described by their
+/// `#EXTINF` line or, failing that, a file name like `Artist - Title.mp3`.
+pub fn parse_m3u(contents: &str) -> (Option<String>, Vec<ImportEntry>) {
+    let mut name = None;
+    let mut entries = Vec::new();
+    let mut info: Option<(Option<u64>, String)> = None;
+
+    for line in contents.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(playlist) = line.strip_prefix("#PLAYLIST:") {
+            name = Some(playlist.trim().to_string());
+        } else if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let (seconds, title) = extinf.split_once(',').unwrap_or((extinf, ""));
+            let seconds = seconds.trim().parse::<i64>().ok().filter(|s| *s >= 0);
+            info = Some((seconds.map(|s| s as u64 * 1000), title.trim().to_string()));
+        } else if !line.starts_with('#') {
+            let (duration_ms, display) = info.take().unwrap_or_else(|| {
+                let file = line.rsplit(['/', '\\']).next().unwrap_or(line);
+                let stem = file.rsplit_once('.').map_or(file, |(stem, _)| stem);
+                (None, stem.to_string())
+            });
+            let (artists, title) = match display.split_once(" - ") {
+                Some((artists, title)) => (Some(artists.trim().to_string()), title.trim().to_string()),
+                None => (None, display),
+            };
+
+            entries.push(ImportEntry {
+                title: Some(title).filter(|title| !title.is_empty()),
+                artists,
+                duration_ms,
+                uri: Some(line.to_string()).filter(|uri| uri.starts_with("spotify:") || uri.contains("open.spotify.com")),
+                ..Default::default()
+            });
+        }
+    }
+
+    (name, entries)
+}
+
+async fn resolve(config: &Config, entry: &ImportEntry, min_confidence: f64) -> Resolution {
+    if let Some(id) = entry.uri.as_ref().and_then(|uri| uri.parse::<TrackId>().ok()) {
+        return Resolution::Uri(id);
+    }
+
+    if let Some(isrc) = &entry.isrc {
+        match search_tracks(config, &format!("isrc:{}", isrc), 1).await {
+            Ok(tracks) if !tracks.is_empty() => return Resolution::Isrc(tracks[0].clone()),
+            Ok(_) => {}
+            Err(why) => return Resolution::Unresolved(why.to_string()),
+        }
+    }
+
+    let title = match &entry.title {
+        Some(title) => title,
+        None => return Resolution::Unresolved("no title, ISRC or Spotify URI".to_string()),
+    };
+    let first_artist = entry
+        .artists
+        .as_deref()
+        .and_then(|artists| artists.split(',').next())
+        .map(str::trim);
+
+    let query = match first_artist {
+        Some(artist) => format!("track:{} artist:{}", normalize(title), artist),
+        None => normalize(title),
+    };
+    let candidates = match search_tracks(config, &query, SEARCH_LIMIT).await {
+        Ok(tracks) if tracks.is_empty() && first_artist.is_some() => {
+            search_tracks(config, &normalize(title), SEARCH_LIMIT).await
+        }
+        result => result,
+    };
+
+    match candidates {
+        Ok(candidates) => best_match(entry, candidates)
+            .filter(|(_, confidence)| *confidence >= min_confidence)
+            .map(|(track, confidence)| Resolution::Fuzzy(track, confidence))
+            .unwrap_or_else(|| Resolution::Unresolved("no confident match".to_string())),
+        Err(why) => Resolution::Unresolved(why.to_string()),
+    }
+}
+
+async fn search_tracks(config: &Config, query: &str, limit: u32) -> Result<Vec<FullTrack>, Box<dyn Error>> {
+    let url = Url::parse_with_params(
+        "https://api.spotify.com/v1/search",
+        &[("q", query), ("type", "track"), ("limit", &limit.to_string())],
+    )?;
+    let result: SearchTracks = get(config, url.as_str()).await?;
+    Ok(result.tracks.items)
+}
+
+/// The candidate most similar to `entry` with its confidence.
+pub(crate) fn best_match(entry: &ImportEntry, candidates: Vec<FullTrack>) -> Option<(FullTrack, f64)> {
+    candidates
+        .into_iter()
+        .map(|track| {
+            let artists: Vec<&str> = track.artists.iter().map(|artist| artist.name.as_str()).collect();
+            let confidence = confidence(entry, &track.name, &artists, track.duration.as_millis() as u64);
+            (track, confidence)
+        })
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// How likely a track with `title`, `artists` and `duration_ms` is the one
+/// described by `entry`, from 0 to 1. Title counts most, then artists, then
+/// duration; whatever the entry lacks is left out.
+pub(crate) fn confidence(entry: &ImportEntry, title: &str, artists: &[&str], duration_ms: u64) -> f64 {
+    let mut score = 0.0;
+    let mut weight = 0.0;
+
+    if let Some(entry_title) = &entry.title {
+        score += 0.5 * normalized_levenshtein(&normalize(entry_title), &normalize(title));
+        weight += 0.5;
+    }
+
+    if let Some(entry_artists) = &entry.artists {
+        let best = entry_artists
+            .split(',')
+            .map(|wanted| {
+                artists
+                    .iter()
+                    .map(|artist| normalized_levenshtein(&normalize(wanted), &normalize(artist)))
+                    .fold(0.0, f64::max)
+            })
+            .fold(0.0, f64::max);
+        score += 0.3 * best;
+        weight += 0.3;
+    }
+
+    if let Some(entry_duration) = entry.duration_ms {
+        // Full marks within 2 seconds, nothing 30 seconds or more apart.
+        let diff = (entry_duration as f64 - duration_ms as f64).abs() / 1000.0;
+        score += 0.2 * (1.0 - ((diff - 2.0).max(0.0) / 28.0).min(1.0));
+        weight += 0.2;
+    }
+
+    if weight == 0.0 {
+        0.0
+    } else {
+        score / weight
+    }
+}
+
+/// Lowercase `value` and drop bracketed parts like "(Remastered 2011)" and
+/// punctuation, which differ between services.
+pub(crate) fn normalize(value: &str) -> String {
+    let mut out = String::new();
+    let mut depth = 0;
+    for c in value.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = (depth - 1).max(0),
+            c if depth == 0 && c.is_alphanumeric() => out.extend(c.to_lowercase()),
+            c if depth == 0 && c.is_whitespace() => out.push(' '),
+            _ => {}
+        }
+    }
+
+    let out = match out.split_once(" remaster") {
+        Some((title, _)) => title.to_string(),
+        None => out,
+    };
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn describe_entry(entry: &ImportEntry) -> String {
+    match (&entry.artists, &entry.title, &entry.uri, &entry.isrc) {
+        (Some(artists), Some(title), _, _) => format!("{} - {}", artists, title),
+        (None, Some(title), _, _) => title.clone(),
+        (_, None, Some(uri), _) => uri.clone(),
+        (_, None, None, Some(isrc)) => format!("isrc:{}", isrc),
+        _ => "(empty entry)".to_string(),
+    }
+}
+
+fn describe_track(track: &FullTrack) -> String {
+    format!("{} - {}", artist_names(&track.artists), track.name.green())
+}
+
+fn write_report(path: &Path, unresolved: &[(ImportEntry, String)]) -> Result<(), Box<dyn Error>> {
+    // The csv crate can't serialize flattened structs, so the entry's fields
+    // are repeated here.
+    #[derive(Serialize)]
+    struct Row<'a> {
+        title: Option<&'a str>,
+        artists: Option<&'a str>,
+        album: Option<&'a str>,
+        duration_ms: Option<u64>,
+        isrc: Option<&'a str>,
+        uri: Option<&'a str>,
+        reason: &'a str,
+    }
+
+    let mut writer = csv::Writer::from_path(path)?;
+    for (entry, reason) in unresolved {
+        writer.serialize(Row {
+            title: entry.title.as_deref(),
+            artists: entry.artists.as_deref(),
+            album: entry.album.as_deref(),
+            duration_ms: entry.duration_ms,
+            isrc: entry.isrc.as_deref(),
+            uri: entry.uri.as_deref(),
+            reason,
+        })?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str, artists: &str, duration_ms: u64) -> ImportEntry {
+        ImportEntry {
+            title: Some(title.to_string()),
+            artists: Some(artists.to_string()),
+            duration_ms: Some(duration_ms),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize("Let It Be (Remastered 2009)"), "let it be");
+        assert_eq!(normalize("Don't Stop Me Now - Remastered 2011"), "dont stop me now");
+        assert_eq!(normalize("  Héroes  [Live] "), "héroes");
+    }
+
+    #[test]
+    fn test_confidence() {
+        let wanted = entry("Bohemian Rhapsody", "Queen", 354_000);
+        let exact = confidence(&wanted, "Bohemian Rhapsody - Remastered 2011", &["Queen"], 355_000);
+        let cover = confidence(&wanted, "Bohemian Rhapsody", &["Pentatonix"], 250_000);
+        let other = confidence(&wanted, "Under Pressure", &["Queen", "David Bowie"], 248_000);
+
+        assert!(exact > 0.99, "{}", exact);
+        assert!(cover < 0.7, "{}", cover);
+        assert!(other < 0.5, "{}", other);
+    }
+
+    #[test]
+    fn test_confidence_missing_fields() {
+        let title_only = ImportEntry {
+            title: Some("Yesterday".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(confidence(&title_only, "Yesterday", &["The Beatles"], 125_000), 1.0);
+        assert_eq!(confidence(&ImportEntry::default(), "Yesterday", &[], 0), 0.0);
+    }
+
+    #[test]
+    fn test_parse_m3u() {
+        let (name, entries) = parse_m3u(
+            "#EXTM3U\n#PLAYLIST:Mix\n#EXTINF:354,Queen - Bohemian Rhapsody\n/music/queen.mp3\n\
+             spotify:track:6rqhFgbbKwnb9MLmUQDhG6\n/music/ABBA - Waterloo.flac\n",
+        );
+
+        assert_eq!(name.as_deref(), Some("Mix"));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], entry("Bohemian Rhapsody", "Queen", 354_000));
+        assert_eq!(entries[1].uri.as_deref(), Some("spotify:track:6rqhFgbbKwnb9MLmUQDhG6"));
+        assert_eq!(entries[2].artists.as_deref(), Some("ABBA"));
+        assert_eq!(entries[2].title.as_deref(), Some("Waterloo"));
+    }
+
+    #[test]
+    fn test_write_report() {
+        let path = std::env::temp_dir().join(format!("spotr-test-report-{}.csv", std::process::id()));
+        let missing = ImportEntry {
+            title: Some("Waterloo".to_string()),
+            isrc: Some("SEAYD7401010".to_string()),
+            ..Default::default()
+        };
+        write_report(&path, &[(missing.clone(), "no confident match".to_string())]).unwrap();
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let (_, entries) = read_entries(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            contents,
+            "title,artists,album,duration_ms,isrc,uri,reason\n\
+             Waterloo,,,,SEAYD7401010,,no confident match\n"
+        );
+        assert_eq!(entries, vec![missing]);
+    }
+}
@@ -0,0 +1,174 @@
+//! Saved tracks, albums and shows
+use std::error::Error;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use colored::Colorize;
+use futures::{future, TryStreamExt};
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use strum::{Display, EnumString};
+
+use super::{currently_playing, get, paginate, paginate_concurrent, PAGE_CONCURRENCY, search::artist_names, send};
+use crate::config::Config;
+use crate::model::{Page, PlayingItem, SavedAlbum, SavedTrack, Show};
+use crate::output::{self, OutputFormat, Record};
+
+/// Part of the library to list: `tracks`, `albums` or `shows`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum LibraryKind {
+    Tracks,
+    Albums,
+    Shows,
+}
+
+/// A saved track, album or show.
+#[derive(Serialize)]
+pub struct LibraryItem {
+    pub added_at: DateTime<Utc>,
+    pub name: String,
+    /// Artists of a track or album, publisher of a show.
+    pub by: String,
+    pub uri: String,
+}
+
+impl Record for LibraryItem {
+    const COLUMNS: &'static [&'static str] = &["added_at", "name", "by", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.added_at.to_rfc3339(),
+            self.name.clone(),
+            self.by.clone(),
+            self.uri.clone(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.added_at.format("%Y-%m-%d").to_string().dimmed(),
+            self.name.green(),
+            self.by,
+            self.uri.dimmed()
+        )
+    }
+}
+
+pub async fn like(config: Config) -> Result<(), Box<dyn Error>> {
+    let (kind, id, name) = current_item(&config).await?;
+    let url = format!("https://api.spotify.com/v1/me/{}?ids={}", kind, id);
+    send(&config, Method::PUT, &url, &json!({})).await?;
+
+    println!("Added {} to your library.", name.green());
+    Ok(())
+}
+
+pub async fn unlike(config: Config) -> Result<(), Box<dyn Error>> {
+    let (kind, id, name) = current_item(&config).await?;
+    let url = format!("https://api.spotify.com/v1/me/{}?ids={}", kind, id);
+    send(&config, Method::DELETE, &url, &json!({})).await?;
+
+    println!("Removed {} from your library.", name.green());
+    Ok(())
+}
+
+/// Whether the current item is in the library.
+pub async fn liked(config: Config) -> Result<bool, Box<dyn Error>> {
+    let (kind, id, name) = current_item(&config).await?;
+    let url = format!("https://api.spotify.com/v1/me/{}/contains?ids={}", kind, id);
+    let saved: Vec<bool> = get(&config, &url).await?;
+    let liked = saved.first().copied().unwrap_or(false);
+
+    if liked {
+        println!("{} is in your library.", name.green());
+    } else {
+        println!("{} is not in your library.", name.yellow());
+    }
+
+    Ok(liked)
+}
+
+/// List saved items of `kind`, optionally only those added on or after
+/// `since`.
+pub async fn library(
+    config: Config,
+    kind: LibraryKind,
+    since: Option<NaiveDate>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let url = format!("https://api.spotify.com/v1/me/{}?limit=50", kind);
+
+    let items = match kind {
+        LibraryKind::Tracks => {
+            saved(config, &url, since, |saved: SavedTrack| LibraryItem {
+                added_at: saved.added_at,
+                by: artist_names(&saved.track.artists),
+                name: saved.track.name,
+                uri: saved.track.uri,
+            })
+            .await?
+        }
+        LibraryKind::Albums => {
+            saved(config, &url, since, |saved: SavedAlbum| LibraryItem {
+                added_at: saved.added_at,
+                by: artist_names(&saved.album.artists),
+                name: saved.album.name,
+                uri: saved.album.uri,
+            })
+            .await?
+        }
+        LibraryKind::Shows => {
+            saved(config, &url, since, |saved: Show| LibraryItem {
+                added_at: saved.added_at,
+                by: saved.show.publisher,
+                name: saved.show.name,
+                uri: saved.show.uri,
+            })
+            .await?
+        }
+    };
+
+    output::print(format, &items)
+}
+
+async fn saved<T>(
+    config: Config,
+    url: &str,
+    since: Option<NaiveDate>,
+    to_item: fn(T) -> LibraryItem,
+) -> Result<Vec<LibraryItem>, Box<dyn Error>>
+where
+    T: DeserializeOwned + 'static,
+{
+    match since {
+        // Saved items come newest first, so stop at the first older one
+        // rather than fetching the whole library.
+        Some(since) => {
+            paginate::<Page<T>>(config, url)
+                .map_ok(to_item)
+                .try_take_while(move |item| future::ready(Ok(item.added_at.naive_utc().date() >= since)))
+                .try_collect()
+                .await
+        }
+        None => {
+            paginate_concurrent::<Page<T>>(config, url, PAGE_CONCURRENCY)
+                .map_ok(to_item)
+                .try_collect()
+                .await
+        }
+    }
+}
+
+/// Endpoint, ID and name of the currently playing track or episode.
+async fn current_item(config: &Config) -> Result<(&'static str, String, String), Box<dyn Error>> {
+    match currently_playing(config).await?.and_then(|playing| playing.item) {
+        Some(PlayingItem::Track(track)) => {
+            let id = track.id.ok_or("Local files can't be saved to your library.")?;
+            Ok(("tracks", id, track.name))
+        }
+        Some(PlayingItem::Episode(episode)) => Ok(("episodes", episode.id, episode.name)),
+        None => Err("Nothing is playing.".into()),
+    }
+}
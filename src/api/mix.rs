@@ -0,0 +1,238 @@
+//! Harmonic mixing: picking and ordering tracks by key and tempo
+use std::error::Error;
+
+use colored::Colorize;
+
+use super::{
+    features::{track_or_current, Camelot},
+    get_audio_features,
+    order::{fetch_items, reorder_to},
+    playlist::playlist_or_default,
+    search::artist_names,
+};
+use crate::config::Config;
+use crate::model::{FullTrack, PlaylistId};
+
+/// Tempo change worth as much as one step around the Camelot wheel, in
+/// percent.
+const PERCENT_PER_STEP: f32 = 2.0;
+
+/// What matters about a track when mixing it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tone {
+    pub camelot: Camelot,
+    pub tempo: f32,
+}
+
+/// Tempo change from `from` to `to` in percent, negative when slowing down,
+/// counting half and double time as the same tempo. Spotify reports a tempo of
+/// 0 when it couldn't detect one, which is infinitely far from any other.
+pub fn tempo_gap(from: f32, to: f32) -> f32 {
+    if from <= 0.0 || to <= 0.0 {
+        return f32::INFINITY;
+    }
+    [to, to * 2.0, to / 2.0]
+        .iter()
+        .map(|to| (to - from) / from * 100.0)
+        .fold(f32::INFINITY, |closest, gap| if gap.abs() < closest.abs() { gap } else { closest })
+}
+
+/// Whether `to` can follow `from`: neighbors on the Camelot wheel within
+/// `max_gap` percent of each other's tempo.
+pub fn compatible(from: Tone, to: Tone, max_gap: f32) -> bool {
+    from.camelot.distance(to.camelot) <= 1 && tempo_gap(from.tempo, to.tempo).abs() <= max_gap
+}
+
+/// How jarring going from `from` to `to` is.
+pub fn transition_cost(from: Tone, to: Tone) -> f32 {
+    from.camelot.distance(to.camelot) as f32 + tempo_gap(from.tempo, to.tempo).abs() / PERCENT_PER_STEP
+}
+
+/// An order of `tones`, as indices, keeping key and tempo jumps small. Starts
+/// with the slowest track and always continues with the closest remaining
+/// one.
+pub fn mix_order(tones: &[Tone]) -> Vec<usize> {
+    let mut remaining: Vec<usize> = (0..tones.len()).collect();
+    let mut order = Vec::with_capacity(tones.len());
+
+    let mut current = match tones
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.tempo.total_cmp(&b.tempo))
+    {
+        Some((slowest, _)) => slowest,
+        None => return order,
+    };
+
+    loop {
+        remaining.retain(|i| *i != current);
+        order.push(current);
+
+        let from = tones[current];
+        current = match remaining
+            .iter()
+            .min_by(|a, b| transition_cost(from, tones[**a]).total_cmp(&transition_cost(from, tones[**b])))
+        {
+            Some(next) => *next,
+            None => return order,
+        };
+    }
+}
+
+fn describe(track: &FullTrack, tone: Tone) -> String {
+    format!(
+        "{:>3} {:>6.1} BPM {} {}",
+        tone.camelot.to_string(),
+        tone.tempo,
+        track.name.green(),
+        artist_names(&track.artists)
+    )
+}
+
+/// Suggest tracks from `playlist`, or the default playlist, that mix well
+/// after the current track.
+pub async fn mix_next(
+    config: Config,
+    playlist: Option<PlaylistId>,
+    max_gap: f32,
+    limit: usize,
+) -> Result<(), Box<dyn Error>> {
+    let playlist = playlist_or_default(&config, playlist)?;
+    let current = track_or_current(&config, None).await?;
+    let (_, items) = fetch_items(&config, &playlist).await?;
+    let tracks: Vec<FullTrack> = items
+        .into_iter()
+        .filter_map(|item| item.track)
+        .filter(|track| track.id.is_some() && track.id != current.id)
+        .collect();
+
+    let mut ids: Vec<String> = tracks.iter().filter_map(|track| track.id.clone()).collect();
+    ids.extend(current.id.clone());
+    let features = get_audio_features(&config, &ids).await?;
+    let tone = |track: &FullTrack| {
+        let features = features.get(track.id.as_ref()?)?;
+        Some(Tone {
+            camelot: Camelot::new(features.key, features.mode)?,
+            tempo: features.tempo,
+        })
+    };
+
+    let from = tone(&current).ok_or("The current track has no key or tempo.")?;
+    println!("{} {}", "Now playing".dimmed(), describe(&current, from));
+
+    let mut suggestions: Vec<(&FullTrack, Tone)> = tracks
+        .iter()
+        .filter_map(|track| Some((track, tone(track)?)))
+        .filter(|(_, to)| compatible(from, *to, max_gap))
+        .collect();
+    suggestions.sort_by(|(_, a), (_, b)| transition_cost(from, *a).total_cmp(&transition_cost(from, *b)));
+
+    if suggestions.is_empty() {
+        println!("No compatible tracks within {}% of the tempo.", max_gap);
+    }
+    for (i, (track, to)) in suggestions.iter().take(limit).enumerate() {
+        let gap = format!("{:+.1}%", tempo_gap(from.tempo, to.tempo));
+        println!("{:>3}. {} {} {}", i + 1, describe(track, *to), gap.dimmed(), track.uri.dimmed());
+    }
+
+    Ok(())
+}
+
+/// Show an order of `playlist` keeping key and tempo jumps small, and reorder
+/// the playlist to it if `apply` is set.
+pub async fn mix_order_playlist(config: Config, playlist: PlaylistId, apply: bool) -> Result<(), Box<dyn Error>> {
+    let (snapshot, items) = fetch_items(&config, &playlist).await?;
+    let ids: Vec<String> = items
+        .iter()
+        .filter_map(|item| item.track.as_ref()?.id.clone())
+        .collect();
+    let features = get_audio_features(&config, &ids).await?;
+
+    // Positions of the tracks with a key and tempo, the others go last.
+    let mut mixable: Vec<(usize, &FullTrack, Tone)> = Vec::new();
+    let mut rest = Vec::new();
+    for (position, item) in items.iter().enumerate() {
+        let tone = item.track.as_ref().and_then(|track| {
+            let features = features.get(track.id.as_ref()?)?;
+            let camelot = Camelot::new(features.key, features.mode)?;
+            Some((track, Tone { camelot, tempo: features.tempo }))
+        });
+        match tone {
+            Some((track, tone)) => mixable.push((position, track, tone)),
+            None => rest.push(position),
+        }
+    }
+
+    let tones: Vec<Tone> = mixable.iter().map(|(_, _, tone)| *tone).collect();
+    let order = mix_order(&tones);
+
+    let mut previous: Option<Tone> = None;
+    let mut jumps = 0;
+    for (i, index) in order.iter().enumerate() {
+        let (_, track, tone) = mixable[*index];
+        let line = format!("{:>3}. {}", i + 1, describe(track, tone));
+        match previous {
+            Some(from) if from.camelot.distance(tone.camelot) > 1 => {
+                jumps += 1;
+                println!("{} {}", line, "(key jump)".yellow());
+            }
+            _ => println!("{}", line),
+        }
+        previous = Some(tone);
+    }
+    if !rest.is_empty() {
+        println!("{} tracks without a key or tempo go last.", rest.len());
+    }
+    println!("{} key jumps.", jumps);
+
+    if !apply {
+        println!("Use --apply to reorder the playlist.");
+        return Ok(());
+    }
+
+    let target: Vec<usize> = order.into_iter().map(|index| mixable[index].0).chain(rest).collect();
+    reorder_to(&config, &playlist, snapshot, &target).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Modality;
+
+    fn tone(key: i32, mode: Modality, tempo: f32) -> Tone {
+        Tone {
+            camelot: Camelot::new(key, mode).unwrap(),
+            tempo,
+        }
+    }
+
+    #[test]
+    fn test_tempo_gap() {
+        assert!((tempo_gap(120.0, 126.0) - 5.0).abs() < 1e-4);
+        assert!((tempo_gap(126.0, 120.0) + 4.7619).abs() < 1e-3);
+        assert!((tempo_gap(140.0, 70.0)).abs() < 1e-4);
+        assert!((tempo_gap(128.0, 66.0) - 3.125).abs() < 1e-4);
+        assert!(tempo_gap(0.0, 120.0).is_infinite());
+        assert!(tempo_gap(120.0, 0.0).is_infinite());
+    }
+
+    #[test]
+    fn test_compatible() {
+        let from = tone(0, Modality::Major, 120.0);
+        assert!(compatible(from, tone(9, Modality::Minor, 124.0), 5.0));
+        assert!(!compatible(from, tone(9, Modality::Minor, 130.0), 5.0));
+        assert!(!compatible(from, tone(2, Modality::Major, 120.0), 5.0));
+    }
+
+    #[test]
+    fn test_mix_order() {
+        let tones = vec![
+            tone(2, Modality::Major, 128.0), // 10B
+            tone(0, Modality::Major, 120.0), // 8B
+            tone(7, Modality::Major, 124.0), // 9B
+            tone(9, Modality::Minor, 121.0), // 8A
+        ];
+        assert_eq!(mix_order(&tones), vec![1, 3, 2, 0]);
+        assert!(mix_order(&[]).is_empty());
+    }
+}
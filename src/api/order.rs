@@ -0,0 +1,259 @@
+//! Sorting and shuffling playlists in place
+//!
+//! Instead of replacing every item, which would reset when each track was
+//! added, the new order is reached by moving as few items as possible.
+use std::{cmp::Ordering, collections::HashSet, error::Error};
+
+use colored::Colorize;
+use futures::TryStreamExt;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::json;
+use strum::{Display, EnumString};
+
+use super::{
+    get_audio_features, paginate_concurrent, PAGE_CONCURRENCY,
+    playlist::{fetch_details, mutate, playlist_url, print_snapshot},
+};
+use crate::config::Config;
+use crate::model::{Page, PlaylistId, PlaylistItem};
+use crate::snapshot::longest_increasing_subsequence;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString, Deserialize)]
+#[strum(serialize_all = "snake_case")]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    AddedAt,
+    Artist,
+    Album,
+    ReleaseDate,
+    Popularity,
+    Tempo,
+    Energy,
+    Key,
+}
+
+impl SortKey {
+    fn needs_audio_features(self) -> bool {
+        matches!(self, SortKey::Tempo | SortKey::Energy | SortKey::Key)
+    }
+}
+
+/// A value items are sorted by.
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum SortValue {
+    Text(String),
+    Number(f64),
+}
+
+/// Move the item at `range_start` to before the item at `insert_before`, both
+/// positions as they are before the move, like Spotify's reorder endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub range_start: usize,
+    pub insert_before: usize,
+}
+
+/// The order of `values` once sorted, as indices into `values`. The sort is
+/// stable and items without a value go last, also when `reverse`d.
+pub fn sorted_order(values: &[Option<SortValue>], reverse: bool) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| match (&values[a], &values[b]) {
+        (Some(a), Some(b)) => {
+            let ordering = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+            if reverse {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+    order
+}
+
+/// Moves turning a list into `target`, where `target[i]` is the current
+/// position of the item that should end up at `i`.
+///
+/// The items of a longest increasing subsequence of `target` are already in
+/// the right order and stay put; every other item is moved once, to just
+/// after the item preceding it in `target`.
+pub fn plan_moves(target: &[usize]) -> Vec<Move> {
+    let stay: HashSet<usize> = longest_increasing_subsequence(target)
+        .into_iter()
+        .map(|i| target[i])
+        .collect();
+    let mut current: Vec<usize> = (0..target.len()).collect();
+    let mut moves = Vec::new();
+
+    for (i, item) in target.iter().enumerate() {
+        if stay.contains(item) {
+            continue;
+        }
+
+        let from = position(&current, *item);
+        let to = if i == 0 { 0 } else { position(&current, target[i - 1]) + 1 };
+        if from == to {
+            continue;
+        }
+
+        moves.push(Move { range_start: from, insert_before: to });
+        current.remove(from);
+        current.insert(if from < to { to - 1 } else { to }, *item);
+    }
+
+    moves
+}
+
+fn position(items: &[usize], item: usize) -> usize {
+    items.iter().position(|i| *i == item).expect("item is in the list")
+}
+
+/// Sort the items of `playlist` by `key`.
+pub async fn sort_playlist(config: Config, playlist: PlaylistId, key: SortKey, reverse: bool) -> Result<(), Box<dyn Error>> {
+    let (snapshot, items) = fetch_items(&config, &playlist).await?;
+
+    let features = if key.needs_audio_features() {
+        let ids: Vec<String> = items
+            .iter()
+            .filter_map(|item| item.track.as_ref()?.id.clone())
+            .collect();
+        get_audio_features(&config, &ids).await?
+    } else {
+        Default::default()
+    };
+
+    let values: Vec<Option<SortValue>> = items
+        .iter()
+        .map(|item| {
+            let track = item.track.as_ref();
+            let features = track.and_then(|track| features.get(track.id.as_ref()?));
+            match key {
+                SortKey::AddedAt => item.added_at.map(|at| SortValue::Text(at.to_rfc3339())),
+                SortKey::Artist => track
+                    .and_then(|track| track.artists.first())
+                    .map(|artist| SortValue::Text(artist.name.to_lowercase())),
+                SortKey::Album => track.map(|track| SortValue::Text(track.album.name.to_lowercase())),
+                SortKey::ReleaseDate => track
+                    .and_then(|track| track.album.release_date.clone())
+                    .map(SortValue::Text),
+                SortKey::Popularity => track.map(|track| SortValue::Number(track.popularity.into())),
+                SortKey::Tempo => features.map(|features| SortValue::Number(features.tempo.into())),
+                SortKey::Energy => features.map(|features| SortValue::Number(features.energy.into())),
+                // -1 means no key was detected.
+                SortKey::Key => features
+                    .filter(|features| features.key >= 0)
+                    .map(|features| SortValue::Number(features.key.into())),
+            }
+        })
+        .collect();
+
+    let target = sorted_order(&values, reverse);
+    reorder_to(&config, &playlist, snapshot, &target).await
+}
+
+/// Shuffle the items of `playlist`. The same `seed` gives the same order.
+pub async fn shuffle_playlist(config: Config, playlist: PlaylistId, seed: Option<u64>) -> Result<(), Box<dyn Error>> {
+    let seed = seed.unwrap_or_else(|| rand::thread_rng().gen());
+    let (snapshot, items) = fetch_items(&config, &playlist).await?;
+
+    let mut target: Vec<usize> = (0..items.len()).collect();
+    target.shuffle(&mut StdRng::seed_from_u64(seed));
+
+    println!("{} {}", "Seed".dimmed(), seed);
+    reorder_to(&config, &playlist, snapshot, &target).await
+}
+
+/// Reorder `playlist` into `target` order, starting from `snapshot`.
+pub(crate) async fn reorder_to(
+    config: &Config,
+    playlist: &PlaylistId,
+    snapshot: String,
+    target: &[usize],
+) -> Result<(), Box<dyn Error>> {
+    let moves = plan_moves(target);
+    if moves.is_empty() {
+        println!("Already in order.");
+        return Ok(());
+    }
+
+    let url = playlist_url(playlist, "/tracks");
+    let mut snapshot = snapshot;
+    for (i, step) in moves.iter().enumerate() {
+        let body = json!({
+            "range_start": step.range_start,
+            "insert_before": step.insert_before,
+            "range_length": 1,
+            "snapshot_id": snapshot,
+        });
+        snapshot = mutate(config, Method::PUT, &url, &body).await?;
+        eprint!("\rMoved {}/{}", i + 1, moves.len());
+    }
+    eprintln!();
+
+    println!("Moved {} of {} items.", moves.len(), target.len());
+    print_snapshot(Some(snapshot));
+    Ok(())
+}
+
+/// Fetch the items of `playlist` along with the snapshot they belong to.
+pub(crate) async fn fetch_items(
+    config: &Config,
+    playlist: &PlaylistId,
+) -> Result<(String, Vec<PlaylistItem>), Box<dyn Error>> {
+    let details = fetch_details(config, playlist).await?;
+    let url = playlist_url(playlist, "/tracks?limit=100");
+    let items = paginate_concurrent::<Page<PlaylistItem>>(config.clone(), &url, PAGE_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    Ok((details.snapshot_id, items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply `moves` the way Spotify does.
+    fn reorder(mut items: Vec<usize>, moves: &[Move]) -> Vec<usize> {
+        for step in moves {
+            let item = items.remove(step.range_start);
+            let to = if step.range_start < step.insert_before {
+                step.insert_before - 1
+            } else {
+                step.insert_before
+            };
+            items.insert(to, item);
+        }
+        items
+    }
+
+    #[test]
+    fn test_plan_moves() {
+        let target = vec![3, 0, 1, 4, 2, 5];
+        let moves = plan_moves(&target);
+        assert_eq!(moves.len(), 2);
+        assert_eq!(reorder((0..6).collect(), &moves), target);
+
+        assert!(plan_moves(&[0, 1, 2]).is_empty());
+
+        let mut target: Vec<usize> = (0..50).collect();
+        target.shuffle(&mut StdRng::seed_from_u64(7));
+        assert_eq!(reorder((0..50).collect(), &plan_moves(&target)), target);
+    }
+
+    #[test]
+    fn test_sorted_order() {
+        let values = vec![
+            Some(SortValue::Number(120.0)),
+            None,
+            Some(SortValue::Number(90.0)),
+            Some(SortValue::Number(120.0)),
+        ];
+        assert_eq!(sorted_order(&values, false), vec![2, 0, 3, 1]);
+        assert_eq!(sorted_order(&values, true), vec![0, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,332 @@
+//! Following paged endpoints
+//!
+//! Spotify returns long lists one page at a time, either with offsets
+//! (`Page`) or with cursors (`CursorBasedPage`), sometimes wrapped in another
+//! object like `{"albums": {...}}`. `paginate` turns any of them into a stream
+//! of items, fetching pages as the stream is polled.
+use std::{error::Error, future::Future};
+
+use futures::future::{self, LocalBoxFuture};
+use futures::stream::{self, LocalBoxStream, StreamExt};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use super::get;
+use crate::config::Config;
+use crate::model::{
+    Category, CategoryPlaylists, CursorBasedPage, CursorPageFullArtists, FeaturedPlaylists,
+    FullArtist, Page, PageCategory, PageSimpliedAlbums, SimplifiedAlbum, SimplifiedPlaylist,
+};
+
+/// Pages, or other requests for many items, fetched at once.
+pub(crate) const PAGE_CONCURRENCY: usize = 4;
+
+/// Stream of items from a paged endpoint.
+pub type PageStream<T> = LocalBoxStream<'static, Result<T, Box<dyn Error>>>;
+
+/// A response holding one page of items.
+pub trait Paginated: Sized {
+    type Item;
+
+    /// URL of the page after this one.
+    fn next_url(&self) -> Option<String>;
+
+    fn into_items(self) -> Vec<Self::Item>;
+
+    /// Offset, limit and total of an offset based page, used to request the
+    /// remaining pages at once.
+    fn offsets(&self) -> Option<(u32, u32, u32)> {
+        None
+    }
+}
+
+impl<T> Paginated for Page<T> {
+    type Item = T;
+
+    fn next_url(&self) -> Option<String> {
+        self.next.clone()
+    }
+
+    fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    fn offsets(&self) -> Option<(u32, u32, u32)> {
+        Some((self.offset, self.limit, self.total))
+    }
+}
+
+impl<T> Paginated for CursorBasedPage<T> {
+    type Item = T;
+
+    fn next_url(&self) -> Option<String> {
+        self.next.clone()
+    }
+
+    fn into_items(self) -> Vec<T> {
+        self.items
+    }
+}
+
+/// Implement `Paginated` for objects wrapping a page in one of their fields.
+macro_rules! wrapped_page {
+    ($($wrapper:ty => $field:ident: $item:ty),* $(,)?) => {
+        $(
+            impl Paginated for $wrapper {
+                type Item = $item;
+
+                fn next_url(&self) -> Option<String> {
+                    self.$field.next_url()
+                }
+
+                fn into_items(self) -> Vec<$item> {
+                    self.$field.into_items()
+                }
+
+                fn offsets(&self) -> Option<(u32, u32, u32)> {
+                    self.$field.offsets()
+                }
+            }
+        )*
+    };
+}
+
+wrapped_page! {
+    PageSimpliedAlbums => albums: SimplifiedAlbum,
+    PageCategory => categories: Category,
+    CategoryPlaylists => playlists: SimplifiedPlaylist,
+    FeaturedPlaylists => playlists: SimplifiedPlaylist,
+    CursorPageFullArtists => artists: FullArtist,
+}
+
+/// Stream every item of the paged endpoint at `url`, one page at a time.
+pub fn paginate<P>(config: Config, url: &str) -> PageStream<P::Item>
+where
+    P: Paginated + DeserializeOwned + 'static,
+{
+    paginate_with(fetcher::<P>(config), url, 1)
+}
+
+/// Like `paginate`, but once the first page of an offset based endpoint
+/// reports its total, up to `concurrency` of the remaining pages are
+/// requested at the same time. Items are still returned in order.
+pub fn paginate_concurrent<P>(config: Config, url: &str, concurrency: usize) -> PageStream<P::Item>
+where
+    P: Paginated + DeserializeOwned + 'static,
+{
+    paginate_with(fetcher::<P>(config), url, concurrency)
+}
+
+/// Fetch pages with an authorized GET request.
+fn fetcher<P>(
+    config: Config,
+) -> impl Fn(String) -> LocalBoxFuture<'static, Result<P, Box<dyn Error>>> + Clone + 'static
+where
+    P: DeserializeOwned + 'static,
+{
+    move |url| {
+        let config = config.clone();
+        Box::pin(async move { get(&config, &url).await })
+    }
+}
+
+fn paginate_with<P, F, Fut>(fetch: F, url: &str, concurrency: usize) -> PageStream<P::Item>
+where
+    P: Paginated + 'static,
+    F: Fn(String) -> Fut + Clone + 'static,
+    Fut: Future<Output = Result<P, Box<dyn Error>>> + 'static,
+{
+    let url = url.to_string();
+    let first = fetch(url.clone());
+
+    stream::once(first)
+        .flat_map(move |page| {
+            let page = match page {
+                Ok(page) => page,
+                Err(why) => return stream::iter(vec![Err(why)]).boxed_local(),
+            };
+
+            match page.offsets() {
+                Some((offset, limit, total)) if concurrency > 1 && limit > 0 => {
+                    let (fetch, url) = (fetch.clone(), url.clone());
+                    let rest = ((offset + limit)..total)
+                        .step_by(limit as usize)
+                        .map(move |offset| {
+                            let params = [("offset", offset.to_string()), ("limit", limit.to_string())];
+                            with_params(&url, &params)
+                        })
+                        .map(move |url| {
+                            let fetch = fetch.clone();
+                            async move { fetch(url?).await.map(Paginated::into_items) }
+                        });
+
+                    // Stop at the first failed page, like `follow` does.
+                    let rest = stream::iter(rest).buffered(concurrency).scan(false, |failed, page| {
+                        if *failed {
+                            return future::ready(None);
+                        }
+                        *failed = page.is_err();
+                        future::ready(Some(page))
+                    });
+
+                    items(Ok(page.into_items()))
+                        .chain(rest.flat_map(items))
+                        .boxed_local()
+                }
+                _ => {
+                    let next = page.next_url();
+                    items(Ok(page.into_items()))
+                        .chain(follow(fetch.clone(), next))
+                        .boxed_local()
+                }
+            }
+        })
+        .boxed_local()
+}
+
+/// Fetch pages one after the other starting at `next`.
+fn follow<P, F, Fut>(fetch: F, next: Option<String>) -> PageStream<P::Item>
+where
+    P: Paginated + 'static,
+    F: Fn(String) -> Fut + 'static,
+    Fut: Future<Output = Result<P, Box<dyn Error>>> + 'static,
+{
+    stream::unfold(next, move |next| {
+        let page = next.map(&fetch);
+
+        async move {
+            match page?.await {
+                Ok(page) => {
+                    let next = page.next_url();
+                    Some((Ok(page.into_items()), next))
+                }
+                Err(why) => Some((Err(why), None)),
+            }
+        }
+    })
+    .flat_map(items)
+    .boxed_local()
+}
+
+fn items<T: 'static>(page: Result<Vec<T>, Box<dyn Error>>) -> PageStream<T> {
+    match page {
+        Ok(items) => stream::iter(items.into_iter().map(Ok)).boxed_local(),
+        Err(why) => stream::iter(vec![Err(why)]).boxed_local(),
+    }
+}
+
+/// `url` with the query parameters in `params` set, replacing existing ones.
+fn with_params(url: &str, params: &[(&str, String)]) -> Result<String, Box<dyn Error>> {
+    let mut url = Url::parse(url)?;
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !params.iter().any(|(param, _)| param == key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(kept)
+        .extend_pairs(params.iter().map(|(key, value)| (*key, value.as_str())));
+
+    Ok(url.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, future, TryStreamExt};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    const URL: &str = "https://api.spotify.com/v1/me/tracks";
+
+    /// A fetcher answering from `pages` instead of the network.
+    fn fake<P: DeserializeOwned>(
+        pages: HashMap<String, Value>,
+    ) -> impl Fn(String) -> future::Ready<Result<P, Box<dyn Error>>> + Clone {
+        move |url| {
+            future::ready(match pages.get(&url) {
+                Some(page) => serde_json::from_value(page.clone()).map_err(Into::into),
+                None => Err(format!("unexpected request for {}", url).into()),
+            })
+        }
+    }
+
+    fn page(offset: u32, items: &[u32], total: u32, next: Option<String>) -> Value {
+        json!({
+            "href": URL, "items": items, "limit": 2, "offset": offset,
+            "next": next, "previous": null, "total": total,
+        })
+    }
+
+    #[test]
+    fn test_offset_pages() {
+        let second = format!("{}?offset=2&limit=2", URL);
+        let third = format!("{}?offset=4&limit=2", URL);
+        let pages: HashMap<_, _> = vec![
+            (URL.to_string(), page(0, &[0, 1], 5, Some(second.clone()))),
+            (second, page(2, &[2, 3], 5, Some(third.clone()))),
+            (third, page(4, &[4], 5, None)),
+        ]
+        .into_iter()
+        .collect();
+
+        for concurrency in &[1, 3] {
+            let stream = paginate_with(fake::<Page<u32>>(pages.clone()), URL, *concurrency);
+            let items: Vec<u32> = block_on(stream.try_collect()).unwrap();
+            assert_eq!(items, vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn test_cursor_pages_in_wrapper() {
+        #[derive(serde::Deserialize)]
+        struct Wrapper {
+            artists: CursorBasedPage<u32>,
+        }
+        wrapped_page! { Wrapper => artists: u32 }
+
+        let cursor = |items: &[u32], next: Option<&str>, after: Option<&str>| {
+            json!({ "artists": {
+                "href": URL, "items": items, "limit": 2, "next": next,
+                "cursors": { "after": after }, "total": 3,
+            }})
+        };
+        // Spotify sends the cursor in `next` too, and no `next` on the last page.
+        let second = format!("{}?type=artist&after=b&limit=2", URL);
+        let pages: HashMap<_, _> = vec![
+            (URL.to_string(), cursor(&[0, 1], Some(&second), Some("b"))),
+            (second.clone(), cursor(&[2], None, None)),
+        ]
+        .into_iter()
+        .collect();
+
+        let items: Vec<u32> = block_on(paginate_with(fake::<Wrapper>(pages), URL, 4).try_collect()).unwrap();
+        assert_eq!(items, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_error_ends_stream() {
+        let pages: HashMap<_, _> = vec![(URL.to_string(), page(0, &[0, 1], 4, Some("missing".into())))]
+            .into_iter()
+            .collect();
+
+        let results: Vec<_> = block_on(paginate_with(fake::<Page<u32>>(pages), URL, 1).collect::<Vec<_>>());
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+
+        // Pages after a failed one aren't returned when fetched concurrently either.
+        let third = format!("{}?offset=4&limit=2", URL);
+        let pages: HashMap<_, _> = vec![
+            (URL.to_string(), page(0, &[0, 1], 6, None)),
+            (third, page(4, &[4, 5], 6, None)),
+        ]
+        .into_iter()
+        .collect();
+
+        let results: Vec<_> = block_on(paginate_with(fake::<Page<u32>>(pages), URL, 3).collect::<Vec<_>>());
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+    }
+}
@@ -0,0 +1,465 @@
+//! Listing and editing playlists
+//!
+//! Every change to a playlist's items creates a new snapshot. Changes report
+//! the new `snapshot_id`, and `remove`/`reorder` accept one so they fail
+//! instead of touching the wrong items if the playlist changed in between.
+use std::error::Error;
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use futures::TryStreamExt;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{get, get_user, paginate, paginate_concurrent, PAGE_CONCURRENCY, search::artist_names, send};
+use crate::config::Config;
+use crate::model::{
+    FullPlaylist, Id, ItemId, Page, PlaylistId, PlaylistItem, PlaylistResult, SimplifiedPlaylist,
+};
+use crate::output::{self, OutputFormat, Record};
+
+/// Most items Spotify accepts in one add or remove request.
+const CHUNK_SIZE: usize = 100;
+
+/// Name, description and current snapshot of a playlist, without its items.
+#[derive(Deserialize)]
+pub(crate) struct PlaylistDetails {
+    pub name: String,
+    pub description: Option<String>,
+    pub snapshot_id: String,
+}
+
+/// One of the user's playlists.
+#[derive(Serialize)]
+pub struct PlaylistRow {
+    pub name: String,
+    pub owner: String,
+    pub tracks: u32,
+    pub uri: String,
+}
+
+impl Record for PlaylistRow {
+    const COLUMNS: &'static [&'static str] = &["name", "owner", "tracks", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.owner.clone(),
+            self.tracks.to_string(),
+            self.uri.clone(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        format!(
+            "{} by {}, {} tracks {}",
+            self.name.green(),
+            self.owner,
+            self.tracks,
+            self.uri.dimmed()
+        )
+    }
+}
+
+impl From<SimplifiedPlaylist> for PlaylistRow {
+    fn from(playlist: SimplifiedPlaylist) -> Self {
+        PlaylistRow {
+            name: playlist.name,
+            owner: playlist.owner.display_name.unwrap_or(playlist.owner.id),
+            tracks: playlist.tracks.total,
+            uri: playlist.uri,
+        }
+    }
+}
+
+/// A track in a playlist, with who added it and when.
+#[derive(Serialize)]
+pub struct PlaylistTrackRow {
+    pub added_at: Option<DateTime<Utc>>,
+    pub added_by: Option<String>,
+    pub name: String,
+    pub artists: String,
+    pub uri: String,
+}
+
+impl Record for PlaylistTrackRow {
+    const COLUMNS: &'static [&'static str] = &["added_at", "added_by", "name", "artists", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.added_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+            self.added_by.clone().unwrap_or_default(),
+            self.name.clone(),
+            self.artists.clone(),
+            self.uri.clone(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        let added_at = self
+            .added_at
+            .map(|at| at.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "----------".to_string());
+        let added_by = self
+            .added_by
+            .as_ref()
+            .map(|by| format!("added by {}", by))
+            .unwrap_or_default();
+
+        format!(
+            "{} {} {} {} {}",
+            added_at.dimmed(),
+            self.name.green(),
+            self.artists,
+            added_by.dimmed(),
+            self.uri.dimmed()
+        )
+    }
+}
+
+impl From<PlaylistItem> for PlaylistTrackRow {
+    fn from(item: PlaylistItem) -> Self {
+        let (name, artists, uri) = match item.track {
+            Some(track) => (track.name.clone(), artist_names(&track.artists), track.uri),
+            None => ("(unavailable)".to_string(), String::new(), String::new()),
+        };
+
+        PlaylistTrackRow {
+            added_at: item.added_at,
+            added_by: item.added_by.map(|user| user.display_name.unwrap_or(user.id)),
+            name,
+            artists,
+            uri,
+        }
+    }
+}
+
+/// List the playlists the user owns or follows.
+pub async fn list_playlists(config: Config, format: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let playlists: Vec<PlaylistRow> = paginate::<Page<SimplifiedPlaylist>>(
+        config,
+        "https://api.spotify.com/v1/me/playlists?limit=50",
+    )
+    .map_ok(PlaylistRow::from)
+    .try_collect()
+    .await?;
+
+    output::print(format, &playlists)
+}
+
+/// List the tracks of `playlist`, or of the default playlist.
+pub async fn show_playlist(
+    config: Config,
+    playlist: Option<PlaylistId>,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let playlist = playlist_or_default(&config, playlist)?;
+    let details: FullPlaylist = get(&config, &playlist_url(&playlist, "")).await?;
+
+    if format == OutputFormat::Text {
+        println!(
+            "{} by {} {}",
+            details.name.green().bold(),
+            details.owner.display_name.as_deref().unwrap_or(&details.owner.id),
+            format!("(snapshot {})", details.snapshot_id).dimmed()
+        );
+        if let Some(description) = details.description.filter(|description| !description.is_empty()) {
+            println!("{}", description);
+        }
+    }
+
+    let url = playlist_url(&playlist, "/tracks?limit=100");
+    let tracks: Vec<PlaylistTrackRow> = paginate_concurrent::<Page<PlaylistItem>>(config, &url, PAGE_CONCURRENCY)
+        .map_ok(PlaylistTrackRow::from)
+        .try_collect()
+        .await?;
+
+    output::print(format, &tracks)
+}
+
+pub async fn create_playlist(
+    config: Config,
+    name: String,
+    description: Option<String>,
+    public: bool,
+    collaborative: bool,
+) -> Result<(), Box<dyn Error>> {
+    let playlist = new_playlist(&config, &name, &description.unwrap_or_default(), public, collaborative).await?;
+    println!("Created {} {}", playlist.name.green(), playlist.uri.dimmed());
+    Ok(())
+}
+
+pub async fn rename_playlist(config: Config, playlist: PlaylistId, name: String) -> Result<(), Box<dyn Error>> {
+    send(&config, Method::PUT, &playlist_url(&playlist, ""), &json!({ "name": name })).await?;
+    println!("Renamed {} to {}.", playlist, name.green());
+    Ok(())
+}
+
+pub async fn describe_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    description: String,
+) -> Result<(), Box<dyn Error>> {
+    let body = json!({ "description": description });
+    send(&config, Method::PUT, &playlist_url(&playlist, ""), &body).await?;
+    println!("Updated the description of {}.", playlist);
+    Ok(())
+}
+
+/// Add tracks or episodes to a playlist, at `position` (1-based) or the end.
+pub async fn add_to_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    items: Vec<ItemId>,
+    position: Option<u32>,
+) -> Result<(), Box<dyn Error>> {
+    let uris = playable_uris(&items)?;
+    let snapshot = add_uris(&config, &playlist, &uris, position).await?;
+
+    println!("Added {} items to {}.", uris.len(), playlist);
+    print_snapshot(snapshot);
+    Ok(())
+}
+
+/// Remove every occurrence of the given tracks or episodes from a playlist.
+pub async fn remove_from_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    items: Vec<ItemId>,
+    snapshot: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let uris = playable_uris(&items)?;
+    let url = playlist_url(&playlist, "/tracks");
+    let mut snapshot = snapshot;
+
+    for chunk in uris.chunks(CHUNK_SIZE) {
+        let body = remove_body(chunk, snapshot.as_deref());
+        snapshot = Some(mutate(&config, Method::DELETE, &url, &body).await?);
+    }
+
+    println!("Removed {} items from {}.", uris.len(), playlist);
+    print_snapshot(snapshot);
+    Ok(())
+}
+
+/// Move `length` items starting at `from` to before `to`, both 1-based as
+/// listed by `sp playlist show`.
+pub async fn reorder_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    from: u32,
+    to: u32,
+    length: u32,
+    snapshot: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let body = reorder_body(from, to, length, snapshot)?;
+    let snapshot = mutate(&config, Method::PUT, &playlist_url(&playlist, "/tracks"), &body).await?;
+    println!("Moved {} items in {}.", length, playlist);
+    print_snapshot(Some(snapshot));
+    Ok(())
+}
+
+pub async fn follow_playlist(config: Config, playlist: PlaylistId) -> Result<(), Box<dyn Error>> {
+    send(&config, Method::PUT, &playlist_url(&playlist, "/followers"), &json!({})).await?;
+    println!("Following {}.", playlist);
+    Ok(())
+}
+
+pub async fn unfollow_playlist(config: Config, playlist: PlaylistId) -> Result<(), Box<dyn Error>> {
+    send(&config, Method::DELETE, &playlist_url(&playlist, "/followers"), &json!({})).await?;
+    println!("Unfollowed {}.", playlist);
+    Ok(())
+}
+
+pub(crate) fn playlist_url(playlist: &PlaylistId, path: &str) -> String {
+    format!("https://api.spotify.com/v1/playlists/{}{}", playlist.id(), path)
+}
+
+pub(crate) async fn fetch_details(config: &Config, playlist: &PlaylistId) -> Result<PlaylistDetails, Box<dyn Error>> {
+    get(config, &playlist_url(playlist, "?fields=name,description,snapshot_id")).await
+}
+
+pub(crate) fn playlist_or_default(config: &Config, playlist: Option<PlaylistId>) -> Result<PlaylistId, Box<dyn Error>> {
+    match playlist {
+        Some(playlist) => Ok(playlist),
+        None => config
+            .default_playlist()?
+            .ok_or_else(|| "No playlist given and no default playlist set, see sp config set playlist.".into()),
+    }
+}
+
+/// Create a playlist owned by the user.
+pub(crate) async fn new_playlist(
+    config: &Config,
+    name: &str,
+    description: &str,
+    public: bool,
+    collaborative: bool,
+) -> Result<FullPlaylist, Box<dyn Error>> {
+    let user = get_user(config).await?;
+    let url = format!("https://api.spotify.com/v1/users/{}/playlists", user.id);
+    let body = json!({
+        "name": name,
+        "description": description,
+        // Collaborative playlists can't be public.
+        "public": public && !collaborative,
+        "collaborative": collaborative,
+    });
+
+    Ok(send(config, Method::POST, &url, &body).await?.json().await?)
+}
+
+/// Add `uris` to a playlist at `position` (1-based) or the end, returning the
+/// last snapshot ID.
+pub(crate) async fn add_uris(
+    config: &Config,
+    playlist: &PlaylistId,
+    uris: &[String],
+    position: Option<u32>,
+) -> Result<Option<String>, Box<dyn Error>> {
+    let url = playlist_url(playlist, "/tracks");
+    let mut snapshot = None;
+
+    for body in add_bodies(uris, position) {
+        snapshot = Some(mutate(config, Method::POST, &url, &body).await?);
+    }
+
+    Ok(snapshot)
+}
+
+/// Bodies adding `uris` in chunks Spotify accepts, each chunk inserted after
+/// the previous one when adding at `position`.
+fn add_bodies(uris: &[String], position: Option<u32>) -> Vec<Value> {
+    uris.chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut body = json!({ "uris": chunk });
+            if let Some(position) = position {
+                body["position"] = json!(position.saturating_sub(1) as usize + i * CHUNK_SIZE);
+            }
+            body
+        })
+        .collect()
+}
+
+/// Body removing every occurrence of `uris`, from `snapshot` if given.
+fn remove_body(uris: &[String], snapshot: Option<&str>) -> Value {
+    let tracks: Vec<Value> = uris.iter().map(|uri| json!({ "uri": uri })).collect();
+    let mut body = json!({ "tracks": tracks });
+    if let Some(snapshot) = snapshot {
+        body["snapshot_id"] = json!(snapshot);
+    }
+    body
+}
+
+/// Body moving items, taking the 1-based positions `sp playlist show` lists.
+fn reorder_body(from: u32, to: u32, length: u32, snapshot: Option<String>) -> Result<Value, Box<dyn Error>> {
+    if from == 0 || to == 0 {
+        return Err("Positions start at 1.".into());
+    }
+
+    let mut body = json!({
+        "range_start": from - 1,
+        "insert_before": to - 1,
+        "range_length": length,
+    });
+    if let Some(snapshot) = snapshot {
+        body["snapshot_id"] = json!(snapshot);
+    }
+    Ok(body)
+}
+
+/// Replace the items of a playlist with `uris`, returning the new snapshot ID.
+pub(crate) async fn replace_uris(
+    config: &Config,
+    playlist: &PlaylistId,
+    uris: &[String],
+) -> Result<String, Box<dyn Error>> {
+    let (first, rest) = uris.split_at(uris.len().min(CHUNK_SIZE));
+    let url = playlist_url(playlist, "/tracks");
+    let snapshot = mutate(config, Method::PUT, &url, &json!({ "uris": first })).await?;
+
+    Ok(add_uris(config, playlist, rest, None).await?.unwrap_or(snapshot))
+}
+
+/// Change the items of a playlist, returning the new snapshot ID.
+pub(crate) async fn mutate(config: &Config, method: Method, url: &str, body: &Value) -> Result<String, Box<dyn Error>> {
+    let result: PlaylistResult = send(config, method, url, body).await?.json().await?;
+    Ok(result.snapshot_id)
+}
+
+fn playable_uris(items: &[ItemId]) -> Result<Vec<String>, Box<dyn Error>> {
+    items
+        .iter()
+        .map(|item| {
+            if item.is_context() {
+                Err(format!("{} is not a track or episode.", item).into())
+            } else {
+                Ok(item.uri())
+            }
+        })
+        .collect()
+}
+
+pub(crate) fn print_snapshot(snapshot: Option<String>) {
+    if let Some(snapshot) = snapshot {
+        println!("{} {}", "Snapshot".dimmed(), snapshot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uris(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("spotify:track:{}", i)).collect()
+    }
+
+    #[test]
+    fn test_add_bodies() {
+        let bodies = add_bodies(&uris(250), Some(3));
+        let sizes: Vec<usize> = bodies.iter().map(|body| body["uris"].as_array().unwrap().len()).collect();
+        assert_eq!(sizes, vec![100, 100, 50]);
+        let positions: Vec<&Value> = bodies.iter().map(|body| &body["position"]).collect();
+        assert_eq!(positions, vec![&json!(2), &json!(102), &json!(202)]);
+        assert_eq!(bodies[2]["uris"][0], json!("spotify:track:200"));
+
+        let bodies = add_bodies(&uris(5), None);
+        assert_eq!(bodies.len(), 1);
+        assert!(bodies[0].get("position").is_none());
+        assert!(add_bodies(&[], None).is_empty());
+    }
+
+    #[test]
+    fn test_remove_and_reorder_bodies() {
+        assert_eq!(
+            remove_body(&uris(2), Some("abc")),
+            json!({
+                "tracks": [{ "uri": "spotify:track:0" }, { "uri": "spotify:track:1" }],
+                "snapshot_id": "abc",
+            })
+        );
+        assert!(remove_body(&uris(1), None).get("snapshot_id").is_none());
+
+        assert_eq!(
+            reorder_body(3, 1, 2, None).unwrap(),
+            json!({ "range_start": 2, "insert_before": 0, "range_length": 2 })
+        );
+        assert!(reorder_body(0, 1, 1, None).is_err());
+    }
+
+    #[test]
+    fn test_playlist_url_and_uris() {
+        let playlist: PlaylistId = "spotify:playlist:37i9dQZF1DXcBWIGoYBM5M".parse().unwrap();
+        assert_eq!(
+            playlist_url(&playlist, "/tracks"),
+            "https://api.spotify.com/v1/playlists/37i9dQZF1DXcBWIGoYBM5M/tracks"
+        );
+
+        let track: ItemId = "spotify:track:6rqhFgbbKwnb9MLmUQDhG6".parse().unwrap();
+        assert_eq!(playable_uris(&[track]).unwrap(), vec!["spotify:track:6rqhFgbbKwnb9MLmUQDhG6"]);
+        let album: ItemId = "spotify:album:6akEvsycLGftJxYudPjmqK".parse().unwrap();
+        assert!(playable_uris(&[album]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+//! Saved podcasts and resuming episodes
+use std::{error::Error, time::Duration};
+
+use colored::Colorize;
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::Method;
+use serde_json::json;
+
+use super::{
+    browse::{length, progress},
+    get, paginate_concurrent, PAGE_CONCURRENCY,
+    recommend::with_device,
+    send,
+};
+use crate::config::Config;
+use crate::model::{EpisodeId, FullEpisode, Id, Page, Show, SimplifiedEpisode, SimplifiedShow};
+
+/// Episodes of every show checked for being unplayed, newest first.
+const RECENT_EPISODES: usize = 50;
+
+/// Episodes that haven't been played to the end.
+pub fn unplayed(episodes: &[SimplifiedEpisode]) -> Vec<&SimplifiedEpisode> {
+    episodes
+        .iter()
+        .filter(|episode| !episode.resume_point.as_ref().map_or(false, |resume| resume.fully_played))
+        .collect()
+}
+
+/// Whether `episode` was started but not finished.
+pub fn is_started(episode: &SimplifiedEpisode) -> bool {
+    episode
+        .resume_point
+        .as_ref()
+        .map_or(false, |resume| !resume.fully_played && resume.resume_position > Duration::default())
+}
+
+/// Saved shows with their most recent episodes.
+async fn saved_shows(config: &Config) -> Result<Vec<(SimplifiedShow, Vec<SimplifiedEpisode>)>, Box<dyn Error>> {
+    let url = "https://api.spotify.com/v1/me/shows?limit=50";
+    let shows: Vec<Show> = paginate_concurrent::<Page<Show>>(config.clone(), url, PAGE_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    stream::iter(shows)
+        .map(|saved| async move {
+            let url = format!(
+                "https://api.spotify.com/v1/shows/{}/episodes?limit={}&market=from_token",
+                saved.show.id, RECENT_EPISODES
+            );
+            let page: Page<SimplifiedEpisode> = get(config, &url).await?;
+            Ok((saved.show, page.items))
+        })
+        .buffered(PAGE_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+/// List saved shows with episodes you haven't finished, showing the newest
+/// `limit` of them.
+pub async fn podcasts(config: Config, limit: usize) -> Result<(), Box<dyn Error>> {
+    let shows = saved_shows(&config).await?;
+
+    let mut caught_up = 0;
+    for (show, episodes) in &shows {
+        let unplayed = unplayed(episodes);
+        if unplayed.is_empty() {
+            caught_up += 1;
+            continue;
+        }
+
+        println!(
+            "{} {} {}",
+            show.name.green().bold(),
+            show.publisher,
+            format!("{} unplayed", unplayed.len()).yellow()
+        );
+        for episode in unplayed.iter().take(limit) {
+            println!(
+                "  {} {} {} {} {}",
+                episode.release_date.dimmed(),
+                episode.name.green(),
+                length(episode.duration),
+                progress(episode.resume_point.as_ref(), episode.duration).yellow(),
+                episode.uri.dimmed()
+            );
+        }
+    }
+    if shows.is_empty() {
+        println!("You haven't saved any shows.");
+    } else if caught_up > 0 {
+        println!("Caught up on {} more shows.", caught_up);
+    }
+
+    Ok(())
+}
+
+/// Play `episode` from where you left off, or the newest episode of your
+/// saved shows you started but didn't finish.
+pub async fn resume_episode(config: Config, episode: Option<EpisodeId>) -> Result<(), Box<dyn Error>> {
+    let (name, uri, show_uri, resume) = match episode {
+        Some(id) => {
+            let url = format!("https://api.spotify.com/v1/episodes/{}?market=from_token", id.id());
+            let episode: FullEpisode = get(&config, &url).await?;
+            (episode.name, episode.uri, episode.show.uri, episode.resume_point)
+        }
+        None => saved_shows(&config)
+            .await?
+            .into_iter()
+            .flat_map(|(show, episodes)| episodes.into_iter().map(move |episode| (show.uri.clone(), episode)))
+            .filter(|(_, episode)| is_started(episode))
+            .max_by(|(_, a), (_, b)| a.release_date.cmp(&b.release_date))
+            .map(|(show_uri, episode)| (episode.name, episode.uri, show_uri, episode.resume_point))
+            .ok_or("No started episodes in your saved shows, give an episode URI.")?,
+    };
+
+    // Spotify leaves out resume points for logins without the scope for them.
+    if resume.is_none() {
+        eprintln!("No resume point for {}, run sp login again if you played it before.", name);
+    }
+
+    // Finished episodes start over.
+    let position = resume
+        .filter(|resume| !resume.fully_played)
+        .map(|resume| resume.resume_position)
+        .unwrap_or_default();
+
+    let url = with_device(&config, "https://api.spotify.com/v1/me/player/play".to_string());
+    let body = json!({
+        "context_uri": show_uri,
+        "offset": { "uri": uri },
+        "position_ms": position.as_millis() as u64,
+    });
+    send(&config, Method::PUT, &url, &body).await?;
+    println!("Resuming {} at {}.", name.green(), length(position));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(resume: serde_json::Value) -> SimplifiedEpisode {
+        serde_json::from_value(json!({
+            "audio_preview_url": null,
+            "description": "",
+            "duration_ms": 1_800_000,
+            "explicit": false,
+            "external_urls": {},
+            "href": "",
+            "id": "",
+            "images": [],
+            "is_externally_hosted": false,
+            "is_playable": true,
+            "language": "en",
+            "languages": ["en"],
+            "name": "",
+            "release_date": "2021-01-01",
+            "release_date_precision": "day",
+            "resume_point": resume,
+            "type": "episode",
+            "uri": "",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_unplayed() {
+        let episodes = vec![
+            episode(json!({ "fully_played": false, "resume_position_ms": 0 })),
+            episode(json!({ "fully_played": false, "resume_position_ms": 60_000 })),
+            episode(json!({ "fully_played": true, "resume_position_ms": 0 })),
+            episode(json!(null)),
+        ];
+        assert_eq!(unplayed(&episodes).len(), 3);
+        let started: Vec<bool> = episodes.iter().map(is_started).collect();
+        assert_eq!(started, vec![false, true, false, false]);
+    }
+}
@@ -0,0 +1,262 @@
+//! Endless radio: keeping the queue topped up with recommendations
+//!
+//! Recommendations are seeded from the tracks played most recently, leaving
+//! out tracks played or queued before and tracks that were skipped quickly.
+//! The radio runs its own polling loop in the foreground, logging plays to the
+//! history as it goes.
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    time::Duration,
+};
+
+use colored::Colorize;
+use tokio::time::sleep;
+
+use super::{
+    currently_playing,
+    features::track_or_current,
+    get,
+    history::sync_log,
+    recommend::{enqueue, fetch_recommendations, play_uris, Seeds, MAX_SEEDS},
+    search::artist_names,
+};
+use crate::config::Config;
+use crate::model::{ItemId, PlayerQueue, PlayingItem, SimplifiedTrack};
+
+/// How often playback and the queue are polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Tracks left before this point count as skipped.
+const SKIP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// What the radio has seen, to pick seeds and avoid repeats.
+#[derive(Clone, Debug, Default)]
+pub struct RadioState {
+    /// Tracks played or queued, oldest first, at most `capacity`.
+    history: VecDeque<String>,
+    capacity: usize,
+    /// Tracks played to past `SKIP_THRESHOLD`, the latest last.
+    recent: VecDeque<String>,
+    skipped: HashSet<String>,
+    /// The track playing at the last poll and how far into it playback was.
+    current: Option<(String, Duration)>,
+}
+
+impl RadioState {
+    pub fn new(capacity: usize) -> Self {
+        RadioState {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    /// Record that `uri` is playing at `progress`. Returns the previous
+    /// track if it was skipped before `SKIP_THRESHOLD`.
+    pub fn observe(&mut self, uri: &str, progress: Duration) -> Option<String> {
+        let previous = self.current.replace((uri.to_string(), progress));
+        match previous {
+            Some((previous, _)) if previous == uri => None,
+            previous => {
+                self.remember(uri);
+                self.recent.push_back(uri.to_string());
+                if self.recent.len() > MAX_SEEDS {
+                    self.recent.pop_front();
+                }
+
+                let (previous, progress) = previous?;
+                if progress >= SKIP_THRESHOLD {
+                    return None;
+                }
+                self.recent.retain(|recent| *recent != previous);
+                self.skipped.insert(previous.clone());
+                Some(previous)
+            }
+        }
+    }
+
+    /// Add `uri` to the history, forgetting the oldest track if it's full.
+    pub fn remember(&mut self, uri: &str) {
+        if self.history.iter().any(|seen| seen == uri) {
+            return;
+        }
+        self.history.push_back(uri.to_string());
+        while self.history.len() > self.capacity {
+            self.history.pop_front();
+        }
+    }
+
+    /// Whether `uri` hasn't been played, queued or skipped, and isn't in
+    /// `queued` either.
+    pub fn is_fresh(&self, uri: &str, queued: &HashSet<String>) -> bool {
+        !self.history.iter().any(|seen| seen == uri) && !self.skipped.contains(uri) && !queued.contains(uri)
+    }
+
+    /// Seeds from the recently played tracks, or `fallback` before any were
+    /// played.
+    pub fn seeds(&self, fallback: &Seeds) -> Seeds {
+        let tracks: Vec<_> = self.recent.iter().filter_map(|uri| uri.parse().ok()).collect();
+        if tracks.is_empty() {
+            return fallback.clone();
+        }
+        Seeds {
+            tracks,
+            ..Default::default()
+        }
+    }
+}
+
+fn item_uri(item: &PlayingItem) -> &str {
+    match item {
+        PlayingItem::Track(track) => &track.uri,
+        PlayingItem::Episode(episode) => &episode.uri,
+    }
+}
+
+/// Keep at least `min_queue` tracks in the queue, adding `batch` at a time,
+/// until interrupted with Ctrl-C. Starts from `seed`, a track or an artist,
+/// or from the current track.
+pub async fn radio(
+    config: Config,
+    seed: Option<ItemId>,
+    min_queue: usize,
+    batch: usize,
+    history: usize,
+) -> Result<(), Box<dyn Error>> {
+    let fallback = match seed {
+        Some(ItemId::Track(id)) => Seeds {
+            tracks: vec![id],
+            ..Default::default()
+        },
+        Some(ItemId::Artist(id)) => Seeds {
+            artists: vec![id],
+            ..Default::default()
+        },
+        Some(item) => return Err(format!("Radio starts from a track or an artist, not {}.", item).into()),
+        None => {
+            let track = track_or_current(&config, None).await?;
+            let id = track.id.ok_or("Local files can't be used as seeds.")?;
+            Seeds {
+                tracks: vec![id.parse()?],
+                ..Default::default()
+            }
+        }
+    };
+
+    println!("Radio on, stop it with Ctrl-C.");
+    let mut state = RadioState::new(history);
+    tokio::select! {
+        result = run(&config, &fallback, &mut state, min_queue, batch) => result,
+        _ = tokio::signal::ctrl_c() => {
+            println!("Radio off.");
+            Ok(())
+        }
+    }
+}
+
+async fn run(
+    config: &Config,
+    fallback: &Seeds,
+    state: &mut RadioState,
+    min_queue: usize,
+    batch: usize,
+) -> Result<(), Box<dyn Error>> {
+    let mut last_uri = None;
+    loop {
+        let playing = currently_playing(config).await?;
+        if let Some(playing) = &playing {
+            if let (Some(PlayingItem::Track(track)), Some(progress)) = (&playing.item, playing.progress) {
+                if let Some(skipped) = state.observe(&track.uri, progress) {
+                    println!("{} {}", "Skipped, avoiding".dimmed(), skipped.dimmed());
+                }
+                // Log the previous track once it's done.
+                if last_uri.as_ref() != Some(&track.uri) {
+                    sync_log(config).await?;
+                    last_uri = Some(track.uri.clone());
+                }
+            }
+        }
+
+        let queue: PlayerQueue = get(config, "https://api.spotify.com/v1/me/player/queue").await?;
+        if playing.is_none() || queue.queue.len() < min_queue {
+            let queued: HashSet<String> = queue.queue.iter().map(|item| item_uri(item).to_string()).collect();
+            let tracks = pick(config, fallback, state, &queued, batch).await?;
+            let uris: Vec<String> = tracks.iter().map(|track| track.uri.clone()).collect();
+
+            if playing.is_none() && !uris.is_empty() {
+                play_uris(config, &uris).await?;
+            } else {
+                for uri in &uris {
+                    enqueue(config, uri).await?;
+                }
+            }
+            for track in &tracks {
+                state.remember(&track.uri);
+                println!("{} {} {}", "Queued".dimmed(), track.name.green(), artist_names(&track.artists));
+            }
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Up to `batch` fresh recommendations for the current seeds.
+async fn pick(
+    config: &Config,
+    fallback: &Seeds,
+    state: &RadioState,
+    queued: &HashSet<String>,
+    batch: usize,
+) -> Result<Vec<SimplifiedTrack>, Box<dyn Error>> {
+    // Ask for extra tracks since some will have been played already.
+    let limit = (batch * 2).clamp(1, 100) as u32;
+    let recommendations = fetch_recommendations(config, &state.seeds(fallback), &[], limit).await?;
+
+    Ok(recommendations
+        .tracks
+        .into_iter()
+        .filter(|track| state.is_fresh(&track.uri, queued))
+        .take(batch)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Id;
+
+    const A: &str = "spotify:track:4uLU6hMCjMI75M1A2tKUQC";
+    const B: &str = "spotify:track:7GhIk7Il098yCjg4BQjzvb";
+    const C: &str = "spotify:track:0eGsygTp906u18L0Oimnem";
+
+    #[test]
+    fn test_observe_skips() {
+        let mut state = RadioState::new(10);
+        assert_eq!(state.observe(A, Duration::from_secs(0)), None);
+        assert_eq!(state.observe(A, Duration::from_secs(40)), None);
+        assert_eq!(state.observe(B, Duration::from_secs(5)), None);
+        assert_eq!(state.observe(C, Duration::from_secs(1)), Some(B.to_string()));
+
+        let seeds = state.seeds(&Seeds::default());
+        let uris: Vec<String> = seeds.tracks.iter().map(|id| id.uri()).collect();
+        assert_eq!(uris, vec![A.to_string(), C.to_string()]);
+        assert!(!state.is_fresh(B, &HashSet::new()));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut state = RadioState::new(2);
+        state.remember(A);
+        state.remember(B);
+        assert!(!state.is_fresh(A, &HashSet::new()));
+        state.remember(C);
+        assert!(state.is_fresh(A, &HashSet::new()));
+        assert!(!state.is_fresh(A, &vec![A.to_string()].into_iter().collect()));
+
+        let fallback = Seeds {
+            genres: vec!["jazz".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(state.seeds(&fallback), fallback);
+    }
+}
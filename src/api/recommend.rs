@@ -0,0 +1,198 @@
+//! Track recommendations
+use std::error::Error;
+
+use colored::Colorize;
+use reqwest::Method;
+use serde_json::json;
+
+use super::{
+    features::track_or_current, get, playlist::add_uris, playlist::print_snapshot,
+    search::artist_names, send,
+};
+use crate::config::Config;
+use crate::model::{ArtistId, Id, PlaylistId, Recommendations, RecommendationsSeedType, TrackId};
+
+/// Most seeds Spotify accepts, across artists, tracks and genres.
+pub const MAX_SEEDS: usize = 5;
+
+/// What recommendations are based on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Seeds {
+    pub artists: Vec<ArtistId>,
+    pub tracks: Vec<TrackId>,
+    pub genres: Vec<String>,
+}
+
+impl Seeds {
+    /// `seed_*` query parameters, failing unless there are 1 to 5 seeds.
+    pub fn params(&self) -> Result<Vec<(&'static str, String)>, Box<dyn Error>> {
+        let count = self.artists.len() + self.tracks.len() + self.genres.len();
+        if count == 0 || count > MAX_SEEDS {
+            return Err(format!("Recommendations take 1 to {} seeds in total, got {}.", MAX_SEEDS, count).into());
+        }
+
+        let join = |ids: Vec<&str>| ids.join(",");
+        let mut params = Vec::new();
+        if !self.artists.is_empty() {
+            params.push(("seed_artists", join(self.artists.iter().map(Id::id).collect())));
+        }
+        if !self.tracks.is_empty() {
+            params.push(("seed_tracks", join(self.tracks.iter().map(Id::id).collect())));
+        }
+        if !self.genres.is_empty() {
+            params.push(("seed_genres", join(self.genres.iter().map(String::as_str).collect())));
+        }
+        Ok(params)
+    }
+}
+
+/// What to do with the recommended tracks besides listing them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecommendAction {
+    List,
+    Play,
+    Queue,
+    ToPlaylist(PlaylistId),
+}
+
+/// Recommend tracks for `seeds`, the current track being one of them if
+/// `seed_current` is set, tuned by `attributes` such as `min_tempo`.
+pub async fn recommend(
+    config: Config,
+    mut seeds: Seeds,
+    seed_current: bool,
+    attributes: Vec<(String, f64)>,
+    limit: u32,
+    action: RecommendAction,
+) -> Result<(), Box<dyn Error>> {
+    if seed_current {
+        let track = track_or_current(&config, None).await?;
+        let id = track.id.ok_or("Local files can't be used as seeds.")?;
+        seeds.tracks.push(id.parse()?);
+    }
+
+    let recommendations = fetch_recommendations(&config, &seeds, &attributes, limit).await?;
+
+    let seeds: Vec<String> = recommendations
+        .seeds
+        .iter()
+        .map(|seed| {
+            let kind = match seed._type {
+                RecommendationsSeedType::Artist => "artist",
+                RecommendationsSeedType::Track => "track",
+                RecommendationsSeedType::Genre => "genre",
+            };
+            format!("{} {} ({} candidates)", kind, seed.id, seed.after_relinking_size)
+        })
+        .collect();
+    println!("{} {}", "Seeds".dimmed(), seeds.join(", "));
+
+    if recommendations.tracks.is_empty() {
+        println!("No recommendations, try fewer or looser attributes.");
+        return Ok(());
+    }
+    for (i, track) in recommendations.tracks.iter().enumerate() {
+        println!(
+            "{:>3}. {} {} {}",
+            i + 1,
+            track.name.green(),
+            artist_names(&track.artists),
+            track.uri.dimmed()
+        );
+    }
+
+    let uris: Vec<String> = recommendations.tracks.into_iter().map(|track| track.uri).collect();
+    match action {
+        RecommendAction::List => {}
+        RecommendAction::Play => {
+            play_uris(&config, &uris).await?;
+            println!("Playing {} recommendations.", uris.len());
+        }
+        RecommendAction::Queue => {
+            for uri in &uris {
+                enqueue(&config, uri).await?;
+            }
+            println!("Queued {} recommendations.", uris.len());
+        }
+        RecommendAction::ToPlaylist(playlist) => {
+            let snapshot = add_uris(&config, &playlist, &uris, None).await?;
+            println!("Added {} recommendations to {}.", uris.len(), playlist);
+            print_snapshot(snapshot);
+        }
+    }
+
+    Ok(())
+}
+
+/// Fetch up to `limit` recommendations for `seeds`, tuned by `attributes`.
+pub(crate) async fn fetch_recommendations(
+    config: &Config,
+    seeds: &Seeds,
+    attributes: &[(String, f64)],
+    limit: u32,
+) -> Result<Recommendations, Box<dyn Error>> {
+    let mut url = url::Url::parse("https://api.spotify.com/v1/recommendations")?;
+    {
+        let mut query = url.query_pairs_mut();
+        query.append_pair("limit", &limit.to_string());
+        for (name, value) in seeds.params()? {
+            query.append_pair(name, &value);
+        }
+        for (name, value) in attributes {
+            query.append_pair(name, &value.to_string());
+        }
+    }
+
+    get(config, url.as_str()).await
+}
+
+/// Play `uris` on the default device.
+pub(crate) async fn play_uris(config: &Config, uris: &[String]) -> Result<(), Box<dyn Error>> {
+    let url = with_device(config, "https://api.spotify.com/v1/me/player/play".to_string());
+    send(config, Method::PUT, &url, &json!({ "uris": uris })).await?;
+    Ok(())
+}
+
+/// Add `uri` to the queue of the default device.
+pub(crate) async fn enqueue(config: &Config, uri: &str) -> Result<(), Box<dyn Error>> {
+    let url = format!("https://api.spotify.com/v1/me/player/queue?uri={}", uri);
+    send(config, Method::POST, &with_device(config, url), &json!({})).await?;
+    Ok(())
+}
+
+/// `url` on the default device, if one is set.
+pub(crate) fn with_device(config: &Config, url: String) -> String {
+    match &config.defaults.device {
+        Some(device) if url.contains('?') => format!("{}&device_id={}", url, device),
+        Some(device) => format!("{}?device_id={}", url, device),
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seed_params() {
+        let seeds = Seeds {
+            artists: vec!["spotify:artist:0OdUWJ0sBjDrqHygGUXeCF".parse().unwrap()],
+            genres: vec!["indie".to_string(), "folk".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            seeds.params().unwrap(),
+            vec![
+                ("seed_artists", "0OdUWJ0sBjDrqHygGUXeCF".to_string()),
+                ("seed_genres", "indie,folk".to_string()),
+            ]
+        );
+
+        assert!(Seeds::default().params().is_err());
+        let too_many = Seeds {
+            genres: vec!["a", "b", "c", "d", "e", "f"].into_iter().map(String::from).collect(),
+            ..Default::default()
+        };
+        assert!(too_many.params().is_err());
+    }
+}
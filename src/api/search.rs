@@ -0,0 +1,102 @@
+//! Catalog search
+use std::error::Error;
+
+use colored::Colorize;
+use url::Url;
+
+use serde::Serialize;
+
+use super::get;
+use crate::config::Config;
+use crate::model::{SearchResult, SearchType, SimplifiedArtist};
+use crate::output::{self, OutputFormat, Record};
+
+/// A search result: the name, who made it and its URI.
+#[derive(Serialize)]
+pub struct SearchRow {
+    pub name: String,
+    pub detail: String,
+    pub uri: String,
+}
+
+impl Record for SearchRow {
+    const COLUMNS: &'static [&'static str] = &["name", "detail", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![self.name.clone(), self.detail.clone(), self.uri.clone()]
+    }
+
+    fn line(&self) -> String {
+        format!("{} {} {}", self.name.green(), self.detail, self.uri.dimmed())
+    }
+}
+
+pub async fn search(
+    config: Config,
+    query: String,
+    kind: SearchType,
+    limit: u32,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let url = Url::parse_with_params(
+        "https://api.spotify.com/v1/search",
+        &[
+            ("q", query),
+            ("type", kind.to_string()),
+            ("limit", limit.to_string()),
+        ],
+    )?;
+
+    let result: SearchResult = get(&config, url.as_str()).await?;
+
+    let rows: Vec<(String, String, String)> = match result {
+        SearchResult::Tracks(page) => page
+            .items
+            .into_iter()
+            .map(|track| (track.name, artist_names(&track.artists), track.uri))
+            .collect(),
+        SearchResult::Albums(page) => page
+            .items
+            .into_iter()
+            .map(|album| (album.name, artist_names(&album.artists), album.uri.unwrap_or_default()))
+            .collect(),
+        SearchResult::Artists(page) => page
+            .items
+            .into_iter()
+            .map(|artist| (artist.name, artist.genres.join(", "), artist.uri))
+            .collect(),
+        SearchResult::Playlists(page) => page
+            .items
+            .into_iter()
+            .map(|playlist| {
+                let owner = playlist.owner.display_name.unwrap_or(playlist.owner.id);
+                (playlist.name, owner, playlist.uri)
+            })
+            .collect(),
+        SearchResult::Shows(page) => page
+            .items
+            .into_iter()
+            .map(|show| (show.name, show.publisher, show.uri))
+            .collect(),
+        SearchResult::Episodes(page) => page
+            .items
+            .into_iter()
+            .map(|episode| (episode.name, episode.release_date, episode.uri))
+            .collect(),
+    };
+
+    let rows: Vec<SearchRow> = rows
+        .into_iter()
+        .map(|(name, detail, uri)| SearchRow { name, detail, uri })
+        .collect();
+
+    output::print(format, &rows)
+}
+
+pub(crate) fn artist_names(artists: &[SimplifiedArtist]) -> String {
+    artists
+        .iter()
+        .map(|artist| artist.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
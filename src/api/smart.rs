@@ -0,0 +1,172 @@
+//! Syncing smart playlists
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+};
+
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use futures::TryStreamExt;
+
+use super::{
+    get_artists, get_audio_features, paginate_concurrent, PAGE_CONCURRENCY,
+    playlist::{new_playlist, playlist_url, print_snapshot, replace_uris},
+    snapshot::{fetch_snapshot, print_changes},
+};
+use crate::config::Config;
+use crate::model::{FullTrack, Page, PlaylistItem, SavedTrack};
+use crate::smart::{self, Candidate, Features, Source};
+use crate::snapshot::{self, SnapshotItem};
+
+/// List the rules in the rules directory.
+pub fn list_smart() -> Result<(), Box<dyn Error>> {
+    let names = smart::list()?;
+    if names.is_empty() {
+        println!("No rules yet, see sp smart sync --help.");
+    }
+
+    for name in names {
+        match smart::rule_path(&name).and_then(|path| smart::load(&path)) {
+            Ok(rule) => println!("{} {}", name.green(), rule.name.dimmed()),
+            Err(why) => println!("{} {}", name.red(), why),
+        }
+    }
+
+    Ok(())
+}
+
+/// Evaluate a rule and write the tracks it selects into its playlist,
+/// creating the playlist on the first sync.
+pub async fn sync_smart(config: Config, rule: String, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let path = smart::rule_path(&rule)?;
+    let rule = smart::load(&path)?;
+
+    let candidates = fetch_candidates(&config, &rule.source, rule.needs_features(), rule.needs_genres()).await?;
+    let items: Vec<SnapshotItem> = rule
+        .evaluate(&candidates, Utc::now())
+        .into_iter()
+        .map(|candidate| SnapshotItem {
+            uri: candidate.uri.clone(),
+            name: candidate.name.clone(),
+            artists: candidate.artists.join(", "),
+            added_at: None,
+            added_by: None,
+        })
+        .collect();
+    println!("{} selects {} tracks.", rule.name.green().bold(), items.len());
+
+    let playlist = match rule.playlist {
+        Some(playlist) => playlist,
+        None if dry_run => {
+            for (i, item) in items.iter().enumerate() {
+                println!("{:>3}. {} {}", i + 1, item.name.green(), item.artists);
+            }
+            return Ok(());
+        }
+        None => {
+            let description = rule.description.as_deref().unwrap_or("Smart playlist synced by SpotR");
+            let created = new_playlist(&config, &rule.name, description, false, false).await?;
+            let playlist = created.uri.parse()?;
+            smart::set_playlist(&path, &playlist)?;
+            println!("Created {} and added it to {}.", created.uri.dimmed(), path.display());
+            playlist
+        }
+    };
+
+    let current = fetch_snapshot(&config, &playlist).await?;
+    let changes = snapshot::diff(&current.items, &items);
+    if changes.is_empty() {
+        println!("Up to date.");
+        return Ok(());
+    }
+
+    print_changes(&changes);
+    if dry_run {
+        println!("Dry run, the playlist wasn't changed.");
+        return Ok(());
+    }
+
+    // Keep what was there in the playlist history.
+    snapshot::save(&current)?;
+    let uris: Vec<String> = items.into_iter().map(|item| item.uri).collect();
+    print_snapshot(Some(replace_uris(&config, &playlist, &uris).await?));
+    Ok(())
+}
+
+/// Fetch the tracks of `source`, with audio features and artist genres if
+/// asked for.
+async fn fetch_candidates(
+    config: &Config,
+    source: &Source,
+    with_features: bool,
+    with_genres: bool,
+) -> Result<Vec<Candidate>, Box<dyn Error>> {
+    let tracks: Vec<(FullTrack, Option<DateTime<Utc>>)> = match source {
+        Source::Liked => {
+            paginate_concurrent::<Page<SavedTrack>>(
+                config.clone(),
+                "https://api.spotify.com/v1/me/tracks?limit=50",
+                PAGE_CONCURRENCY,
+            )
+            .map_ok(|saved| (saved.track, Some(saved.added_at)))
+            .try_collect()
+            .await?
+        }
+        Source::Playlist(playlist) => {
+            let url = playlist_url(playlist, "/tracks?limit=100");
+            let items: Vec<PlaylistItem> =
+                paginate_concurrent::<Page<PlaylistItem>>(config.clone(), &url, PAGE_CONCURRENCY)
+                    .try_collect()
+                    .await?;
+            items
+                .into_iter()
+                .filter_map(|item| Some((item.track?, item.added_at)))
+                .collect()
+        }
+    };
+    // Local files have no ID, so nothing to look up and nothing to add back.
+    let tracks: Vec<_> = tracks.into_iter().filter(|(track, _)| !track.is_local).collect();
+
+    let features = if with_features {
+        let ids: Vec<String> = tracks.iter().filter_map(|(track, _)| track.id.clone()).collect();
+        get_audio_features(config, &ids).await?
+    } else {
+        HashMap::new()
+    };
+
+    let genres: HashMap<String, Vec<String>> = if with_genres {
+        let ids: HashSet<String> = tracks
+            .iter()
+            .flat_map(|(track, _)| track.artists.iter().filter_map(|artist| artist.id.clone()))
+            .collect();
+        let ids: Vec<String> = ids.into_iter().collect();
+        get_artists(config, &ids)
+            .await?
+            .into_iter()
+            .map(|artist| (artist.id, artist.genres))
+            .collect()
+    } else {
+        HashMap::new()
+    };
+
+    Ok(tracks
+        .into_iter()
+        .map(|(track, added_at)| Candidate {
+            features: track.id.as_ref().and_then(|id| features.get(id)).map(Features::from),
+            genres: track
+                .artists
+                .iter()
+                .filter_map(|artist| genres.get(artist.id.as_ref()?))
+                .flatten()
+                .cloned()
+                .collect(),
+            artists: track.artists.into_iter().map(|artist| artist.name).collect(),
+            uri: track.uri,
+            name: track.name,
+            album: track.album.name,
+            release_date: track.album.release_date,
+            popularity: track.popularity,
+            added_at,
+        })
+        .collect())
+}
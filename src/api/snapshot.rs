@@ -0,0 +1,205 @@
+//! Storing, diffing and restoring playlist snapshots
+use std::error::Error;
+
+use chrono::Utc;
+use colored::Colorize;
+use futures::{future, TryStreamExt};
+
+use super::{
+    paginate_concurrent,
+    playlist::{fetch_details, playlist_url, print_snapshot, replace_uris},
+    search::artist_names,
+    PAGE_CONCURRENCY,
+};
+use crate::config::Config;
+use crate::model::{Id, Page, PlaylistId, PlaylistItem};
+use crate::snapshot::{self, Change, Snapshot, SnapshotItem};
+
+/// Store the current items of `playlist`.
+pub async fn snapshot_playlist(config: Config, playlist: PlaylistId) -> Result<(), Box<dyn Error>> {
+    let current = fetch_snapshot(&config, &playlist).await?;
+
+    if snapshot::save(&current)? {
+        println!(
+            "Stored snapshot {} of {}, {} tracks.",
+            current.snapshot_id.dimmed(),
+            current.name.green(),
+            current.items.len()
+        );
+    } else {
+        println!("Snapshot {} is already stored.", current.snapshot_id.dimmed());
+    }
+
+    Ok(())
+}
+
+/// List the stored snapshots of `playlist`.
+pub async fn playlist_history(config: Config, playlist: PlaylistId) -> Result<(), Box<dyn Error>> {
+    let snapshots = snapshot::list(playlist.id())?;
+    if snapshots.is_empty() {
+        println!("No stored snapshots, take one with sp playlist snapshot {}.", playlist);
+        return Ok(());
+    }
+
+    let live = fetch_details(&config, &playlist).await?;
+    println!("{}", live.name.green().bold());
+    for snapshot in &snapshots {
+        let current = if snapshot.snapshot_id == live.snapshot_id {
+            " (current)".green().to_string()
+        } else {
+            String::new()
+        };
+        println!(
+            "{} {} {:>5} tracks{}",
+            snapshot.taken_at.format("%Y-%m-%d %H:%M").to_string().dimmed(),
+            snapshot.snapshot_id,
+            snapshot.items.len(),
+            current
+        );
+    }
+
+    Ok(())
+}
+
+/// Show what changed between two snapshots, by default between the latest
+/// stored snapshot and the playlist as it is now.
+pub async fn diff_playlist(
+    config: Config,
+    playlist: PlaylistId,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let to = match to {
+        Some(to) => snapshot::find(playlist.id(), &to)?,
+        None => {
+            let current = fetch_snapshot(&config, &playlist).await?;
+            snapshot::save(&current)?;
+            current
+        }
+    };
+    let from = match from {
+        Some(from) => snapshot::find(playlist.id(), &from)?,
+        // The newest one taken before `to`, later ones would diff backwards.
+        None => snapshot::list(playlist.id())?
+            .into_iter()
+            .rev()
+            .find(|snapshot| snapshot.snapshot_id != to.snapshot_id && snapshot.taken_at < to.taken_at)
+            .ok_or_else(|| format!("No earlier snapshot stored, take one with sp playlist snapshot {}.", playlist))?,
+    };
+
+    println!(
+        "{} from {} ({}) to {} ({})",
+        to.name.green().bold(),
+        from.snapshot_id.dimmed(),
+        from.taken_at.format("%Y-%m-%d %H:%M"),
+        to.snapshot_id.dimmed(),
+        to.taken_at.format("%Y-%m-%d %H:%M")
+    );
+
+    let changes = snapshot::diff(&from.items, &to.items);
+    if changes.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    print_changes(&changes);
+    Ok(())
+}
+
+/// Replace the items of `playlist` with those of a stored snapshot. The
+/// current items are stored first so the restore can be undone.
+pub async fn restore_playlist(config: Config, playlist: PlaylistId, snapshot_id: String) -> Result<(), Box<dyn Error>> {
+    let target = snapshot::find(playlist.id(), &snapshot_id)?;
+
+    let current = fetch_snapshot(&config, &playlist).await?;
+    if snapshot::save(&current)? {
+        println!("Stored the current items as snapshot {}.", current.snapshot_id.dimmed());
+    }
+
+    // Local files can't be added through the API.
+    let uris: Vec<String> = target
+        .items
+        .iter()
+        .map(|item| item.uri.clone())
+        .filter(|uri| !uri.starts_with("spotify:local:"))
+        .collect();
+    let skipped = target.items.len() - uris.len();
+
+    let new_snapshot = replace_uris(&config, &playlist, &uris).await?;
+
+    println!(
+        "Restored {} to snapshot {}, {} tracks.",
+        target.name.green(),
+        target.snapshot_id.dimmed(),
+        uris.len()
+    );
+    if skipped > 0 {
+        println!("{} local files couldn't be restored.", skipped.to_string().yellow());
+    }
+    print_snapshot(Some(new_snapshot));
+    Ok(())
+}
+
+/// Print `changes` followed by how many items were added, removed and moved.
+pub(crate) fn print_changes(changes: &[Change]) {
+    for change in changes {
+        match change {
+            Change::Added { item, position } => {
+                let mut by = String::new();
+                if let Some(added_by) = &item.added_by {
+                    by.push_str(&format!(" added by {}", added_by));
+                }
+                if let Some(added_at) = &item.added_at {
+                    by.push_str(&format!(" on {}", added_at.format("%Y-%m-%d")));
+                }
+                println!("{} {:>4}. {}{}", "+".green(), position + 1, describe(item), by.dimmed());
+            }
+            Change::Removed { item, position } => {
+                println!("{} {:>4}. {}", "-".red(), position + 1, describe(item));
+            }
+            Change::Moved { item, from, to } => {
+                let was = format!("(was {})", from + 1);
+                println!("{} {:>4}. {} {}", "~".yellow(), to + 1, describe(item), was.dimmed());
+            }
+        }
+    }
+
+    let count = |kind: fn(&Change) -> bool| changes.iter().filter(|change| kind(change)).count();
+    println!(
+        "{} added, {} removed, {} moved.",
+        count(|change| matches!(change, Change::Added { .. })),
+        count(|change| matches!(change, Change::Removed { .. })),
+        count(|change| matches!(change, Change::Moved { .. }))
+    );
+}
+
+/// Fetch the current items of `playlist`.
+pub(crate) async fn fetch_snapshot(config: &Config, playlist: &PlaylistId) -> Result<Snapshot, Box<dyn Error>> {
+    let details = fetch_details(config, playlist).await?;
+    let url = playlist_url(playlist, "/tracks?limit=100");
+    let items: Vec<SnapshotItem> = paginate_concurrent::<Page<PlaylistItem>>(config.clone(), &url, PAGE_CONCURRENCY)
+        .try_filter_map(|item| {
+            let PlaylistItem { added_at, added_by, track, .. } = item;
+            future::ready(Ok(track.map(|track| SnapshotItem {
+                artists: artist_names(&track.artists),
+                name: track.name,
+                uri: track.uri,
+                added_at,
+                added_by: added_by.map(|user| user.display_name.unwrap_or(user.id)),
+            })))
+        })
+        .try_collect()
+        .await?;
+
+    Ok(Snapshot {
+        playlist_id: playlist.id().to_string(),
+        name: details.name,
+        snapshot_id: details.snapshot_id,
+        taken_at: Utc::now(),
+        items,
+    })
+}
+
+fn describe(item: &SnapshotItem) -> String {
+    format!("{} {}", item.name.green(), item.artists)
+}
@@ -0,0 +1,190 @@
+//! Listening statistics report, computed offline from the local history log
+use std::error::Error;
+
+use chrono::{Local, NaiveDate};
+use colored::Colorize;
+use serde::Serialize;
+
+use super::{features::bar, visualize::level};
+use crate::history;
+use crate::output::{self, OutputFormat, Record};
+use crate::stats::{self, Stats, Tally};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Width of the genre and audio feature bars.
+const BAR_WIDTH: usize = 20;
+
+/// A line of the CSV report.
+#[derive(Serialize)]
+pub struct StatRow {
+    /// total, track, artist, album, genre, heatmap or feature.
+    pub section: &'static str,
+    pub name: String,
+    pub detail: String,
+    pub plays: usize,
+    pub minutes: u64,
+}
+
+impl Record for StatRow {
+    const COLUMNS: &'static [&'static str] = &["section", "name", "detail", "plays", "minutes"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.section.to_string(),
+            self.name.clone(),
+            self.detail.clone(),
+            self.plays.to_string(),
+            self.minutes.to_string(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        format!("{} {} {} {}", self.section.dimmed(), self.name.green(), self.detail, self.plays)
+    }
+}
+
+fn rows(stats: &Stats) -> Vec<StatRow> {
+    let mut rows = vec![StatRow {
+        section: "total",
+        name: String::new(),
+        detail: String::new(),
+        plays: stats.plays,
+        minutes: stats::minutes(stats.duration_ms),
+    }];
+    for (section, tallies) in &[
+        ("track", &stats.tracks),
+        ("artist", &stats.artists),
+        ("album", &stats.albums),
+        ("genre", &stats.genres),
+    ] {
+        rows.extend(tallies.iter().map(|tally| StatRow {
+            section,
+            name: tally.name.clone(),
+            detail: tally.detail.clone(),
+            plays: tally.plays,
+            minutes: stats::minutes(tally.duration_ms),
+        }));
+    }
+    for (day, hours) in WEEKDAYS.iter().zip(&stats.heatmap) {
+        rows.extend(hours.iter().enumerate().map(|(hour, plays)| StatRow {
+            section: "heatmap",
+            name: day.to_string(),
+            detail: format!("{:02}:00", hour),
+            plays: *plays,
+            minutes: 0,
+        }));
+    }
+    if let Some(features) = &stats.features {
+        for (name, value) in &[
+            ("energy", features.energy),
+            ("danceability", features.danceability),
+            ("valence", features.valence),
+            ("tempo", features.tempo),
+        ] {
+            rows.push(StatRow {
+                section: "feature",
+                name: name.to_string(),
+                detail: format!("{:.3}", value),
+                plays: features.plays,
+                minutes: 0,
+            });
+        }
+    }
+    rows
+}
+
+fn print_tallies(title: &str, tallies: &[Tally]) {
+    if tallies.is_empty() {
+        return;
+    }
+    println!("{}", title.bold());
+    for (i, tally) in tallies.iter().enumerate() {
+        println!(
+            "{:>3}. {} {} {}",
+            i + 1,
+            tally.name.green(),
+            tally.detail,
+            format!("{} plays, {} min", tally.plays, stats::minutes(tally.duration_ms)).dimmed()
+        );
+    }
+}
+
+fn print_report(stats: &Stats) {
+    println!(
+        "{} plays, {} minutes ({:.1} hours)",
+        stats.plays.to_string().bold(),
+        stats::minutes(stats.duration_ms).to_string().bold(),
+        stats.duration_ms as f64 / 3_600_000.0
+    );
+
+    print_tallies("Top tracks", &stats.tracks);
+    print_tallies("Top artists", &stats.artists);
+    print_tallies("Top albums", &stats.albums);
+
+    if !stats.genres.is_empty() {
+        println!("{}", "Genres".bold());
+        for genre in &stats.genres {
+            let share = genre.plays as f32 / stats.plays as f32;
+            println!(
+                "{:>24} {} {:.0}%",
+                genre.name,
+                bar(share, BAR_WIDTH).green(),
+                share * 100.0
+            );
+        }
+    }
+
+    // Each cell is shaded relative to the busiest hour of the week.
+    let busiest = stats.heatmap.iter().flatten().copied().max().unwrap_or(0).max(1);
+    println!("{}", "When you listen".bold());
+    println!("{:>4} {}", "", "0     6     12    18    ".dimmed());
+    for (day, hours) in WEEKDAYS.iter().zip(&stats.heatmap) {
+        let cells: String = hours
+            .iter()
+            .map(|plays| if *plays == 0 { ' ' } else { level(*plays as f32 / busiest as f32) })
+            .collect();
+        println!("{:>4} {} {}", day, cells.cyan(), hours.iter().sum::<usize>());
+    }
+
+    if let Some(features) = &stats.features {
+        println!("{} {}", "Average audio features".bold(), format!("of {} plays", features.plays).dimmed());
+        for (name, value) in &[
+            ("Energy", features.energy),
+            ("Danceability", features.danceability),
+            ("Valence", features.valence),
+        ] {
+            println!("{:>24} {} {:.2}", name, bar(*value, BAR_WIDTH).green(), value);
+        }
+        println!("{:>24} {:.1} BPM", "Tempo", features.tempo);
+    }
+}
+
+/// Report on the plays logged for `profile` between `since` and `until`,
+/// both inclusive, without contacting Spotify.
+pub fn stats(
+    profile: &str,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    top: usize,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let mut plays = history::load(profile)?;
+    plays.retain(|play| {
+        let date = play.played_at.with_timezone(&Local).date().naive_local();
+        since.map_or(true, |since| date >= since) && until.map_or(true, |until| date <= until)
+    });
+    if plays.is_empty() && format == OutputFormat::Text {
+        println!("No plays logged in that time, use sp history sync to log them.");
+        return Ok(());
+    }
+
+    let stats = stats::compute(&plays, &history::load_metadata()?, &Local, top);
+    match format {
+        OutputFormat::Text => print_report(&stats),
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+        OutputFormat::Csv => output::print(format, &rows(&stats))?,
+    }
+
+    Ok(())
+}
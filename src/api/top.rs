@@ -0,0 +1,166 @@
+//! Your top tracks and artists
+use std::error::Error;
+
+use chrono::Utc;
+use colored::Colorize;
+use futures::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use strum::{Display, EnumString};
+
+use super::{paginate, search::artist_names};
+use crate::config::Config;
+use crate::model::{FullArtist, FullTrack, Page, TimeRange};
+use crate::output::{self, OutputFormat, Record};
+use crate::top::{self, RankChange, Ranked, Ranking};
+
+/// What to rank: `tracks` or `artists`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum TopKind {
+    Tracks,
+    Artists,
+}
+
+/// A top track or artist with its rank.
+#[derive(Serialize)]
+pub struct TopRow {
+    pub rank: usize,
+    pub name: String,
+    pub detail: String,
+    pub uri: String,
+}
+
+impl Record for TopRow {
+    const COLUMNS: &'static [&'static str] = &["rank", "name", "detail", "uri"];
+
+    fn values(&self) -> Vec<String> {
+        vec![
+            self.rank.to_string(),
+            self.name.clone(),
+            self.detail.clone(),
+            self.uri.clone(),
+        ]
+    }
+
+    fn line(&self) -> String {
+        format!("{} {} {}", self.name.green(), self.detail, self.uri.dimmed())
+    }
+}
+
+fn describe(range: TimeRange) -> &'static str {
+    match range {
+        TimeRange::ShortTerm => "last 4 weeks",
+        TimeRange::MediumTerm => "last 6 months",
+        TimeRange::LongTerm => "all time",
+    }
+}
+
+/// The current top `limit` items of `kind` over `range`, best first.
+async fn fetch_ranking(
+    config: &Config,
+    kind: TopKind,
+    range: TimeRange,
+    limit: usize,
+) -> Result<Vec<Ranked>, Box<dyn Error>> {
+    let url = format!(
+        "https://api.spotify.com/v1/me/top/{}?time_range={}&limit={}",
+        kind,
+        range.to_string(),
+        limit.clamp(1, 50)
+    );
+
+    // Spotify pages through up to the top 99 items.
+    let ranking = match kind {
+        TopKind::Tracks => paginate::<Page<FullTrack>>(config.clone(), &url)
+            .map_ok(|track| Ranked {
+                detail: artist_names(&track.artists),
+                name: track.name,
+                uri: track.uri,
+            })
+            .take(limit)
+            .try_collect()
+            .await?,
+        TopKind::Artists => paginate::<Page<FullArtist>>(config.clone(), &url)
+            .map_ok(|artist| Ranked {
+                detail: artist.genres.join(", "),
+                name: artist.name,
+                uri: artist.uri,
+            })
+            .take(limit)
+            .try_collect()
+            .await?,
+    };
+    Ok(ranking)
+}
+
+/// List your top `limit` tracks or artists over `range`.
+pub async fn top(
+    config: Config,
+    kind: TopKind,
+    range: TimeRange,
+    limit: usize,
+    format: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let ranking = fetch_ranking(&config, kind, range, limit).await?;
+    let rows: Vec<TopRow> = ranking
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| TopRow {
+            rank: i + 1,
+            name: item.name,
+            detail: item.detail,
+            uri: item.uri,
+        })
+        .collect();
+
+    output::print(format, &rows)
+}
+
+/// Compare your top `limit` tracks or artists over `range` with the ranking
+/// saved by the previous diff, then save the current one.
+pub async fn top_diff(config: Config, kind: TopKind, range: TimeRange, limit: usize) -> Result<(), Box<dyn Error>> {
+    let name = format!("{}_{}", kind, range.to_string());
+    let current = Ranking {
+        taken_at: Utc::now(),
+        items: fetch_ranking(&config, kind, range, limit).await?,
+    };
+
+    let previous = match top::load(&config.profile, &name)? {
+        Some(previous) => previous,
+        None => {
+            top::save(&config.profile, &name, &current)?;
+            println!("Saved your current top {}, run this again later to see what changed.", kind);
+            return Ok(());
+        }
+    };
+
+    println!(
+        "Top {} ({}) compared with {}:",
+        kind,
+        describe(range),
+        previous.taken_at.format("%Y-%m-%d")
+    );
+    let changes = top::diff(&previous.items, &current.items);
+    if changes.is_empty() {
+        println!("No changes.");
+    }
+    for change in &changes {
+        let (rank, marker, item) = match change {
+            RankChange::Climbed { item, from, to } => (to.to_string(), format!("▲{}", from - to), item),
+            RankChange::Fell { item, from, to } => (to.to_string(), format!("▼{}", to - from), item),
+            RankChange::New { item, rank } => (rank.to_string(), "new".to_string(), item),
+            RankChange::Dropped { item, rank } => ("–".to_string(), format!("was {}", rank), item),
+        };
+        // Pad before coloring, the escape codes would count towards the width.
+        let marker = format!("{:<7}", marker);
+        let marker = match change {
+            RankChange::Climbed { .. } => marker.green(),
+            RankChange::Fell { .. } => marker.red(),
+            RankChange::New { .. } => marker.yellow(),
+            RankChange::Dropped { .. } => marker.dimmed(),
+        };
+        println!("{:>4} {} {} {}", rank, marker, item.name.green(), item.detail);
+    }
+
+    top::save(&config.profile, &name, &current)
+}
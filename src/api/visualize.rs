@@ -0,0 +1,241 @@
+//! Terminal visualization of the current track, driven by its audio analysis
+//!
+//! Playback is polled every few seconds and extrapolated in between, so the
+//! visualization follows seeks, pauses and track changes.
+use std::{
+    error::Error,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use tokio::time::sleep;
+
+use super::{
+    currently_playing,
+    features::{bar, key_name, PITCH_CLASSES},
+    get,
+    search::artist_names,
+};
+use crate::config::Config;
+use crate::model::{AudioAnalysis, PlayingItem, TimeInterval};
+
+/// How often playback is polled.
+const SYNC_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Time between frames.
+const FRAME: Duration = Duration::from_millis(50);
+
+/// Width of the bars and of the timeline.
+const WIDTH: usize = 48;
+
+/// Segments shown in the loudness envelope, centered on the current one.
+const ENVELOPE: usize = 48;
+
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Index of the interval containing `time`, the last one starting before it.
+pub fn find_index<T>(intervals: &[T], time: f32, start: fn(&T) -> f32) -> Option<usize> {
+    intervals.partition_point(|interval| start(interval) <= time).checked_sub(1)
+}
+
+/// `value` between 0 and 1 as one of eight block heights.
+pub fn level(value: f32) -> char {
+    let index = (value.clamp(0.0, 1.0) * (LEVELS.len() - 1) as f32).round() as usize;
+    LEVELS[index]
+}
+
+/// Loudness in dB scaled to 0–1, -60 dB and below being silence.
+pub fn loudness_level(db: f32) -> f32 {
+    ((db + 60.0) / 60.0).clamp(0.0, 1.0)
+}
+
+/// How strongly a beat pulses at `time`, 1 at its start fading to 0 at its end.
+pub fn pulse(beat: &TimeInterval, time: f32) -> f32 {
+    if beat.duration <= 0.0 {
+        return 0.0;
+    }
+    1.0 - ((time - beat.start) / beat.duration).clamp(0.0, 1.0)
+}
+
+/// The track as a line of `width` characters, marking where sections start
+/// and where `time` is.
+pub fn timeline(sections: &[f32], duration: f32, time: f32, width: usize) -> String {
+    let column = |at: f32| ((at / duration * width as f32) as usize).min(width - 1);
+    let mut line = vec!['─'; width];
+    for start in sections.iter().skip(1) {
+        line[column(*start)] = '┆';
+    }
+    line[column(time)] = '●';
+    line.into_iter().collect()
+}
+
+/// Lines of one frame at `time` seconds into the track.
+fn render(analysis: &AudioAnalysis, title: &str, time: f32) -> Vec<String> {
+    let start = |interval: &TimeInterval| interval.start;
+    let duration = analysis.track.duration;
+
+    let section = find_index(&analysis.sections, time, |section| section.time_interval.start);
+    let (tempo, key) = section
+        .map(|i| &analysis.sections[i])
+        .map(|section| (section.tempo, key_name(section.key, section.mode)))
+        .unwrap_or((analysis.track.tempo, None));
+
+    let beat = find_index(&analysis.beats, time, start).map(|i| &analysis.beats[i]);
+    let strength = beat.map_or(0.0, |beat| pulse(beat, time));
+    let bar_number = find_index(&analysis.bars, time, start).map_or(0, |i| i + 1);
+
+    let segment = find_index(&analysis.segments, time, |segment| segment.time_interval.start);
+    let loudness = segment.map_or(-60.0, |i| analysis.segments[i].loudness_max);
+    let envelope: String = match segment {
+        Some(current) => (0..ENVELOPE)
+            .map(|offset| (current + offset).checked_sub(ENVELOPE / 2))
+            .map(|i| match i.and_then(|i| analysis.segments.get(i)) {
+                Some(segment) => level(loudness_level(segment.loudness_max)),
+                None => ' ',
+            })
+            .collect(),
+        None => " ".repeat(ENVELOPE),
+    };
+    let chroma: Vec<String> = match segment.map(|i| &analysis.segments[i].pitches) {
+        Some(pitches) => pitches
+            .iter()
+            .map(|pitch| format!("{:<3}", level(*pitch).to_string().repeat(2)))
+            .collect(),
+        None => vec!["   ".to_string(); 12],
+    };
+
+    let sections: Vec<f32> = analysis.sections.iter().map(|section| section.time_interval.start).collect();
+    let clock = |seconds: f32| format!("{}:{:02}", seconds as u32 / 60, seconds as u32 % 60);
+
+    vec![
+        title.to_string(),
+        format!(
+            "{} / {}  section {}/{}  {:.1} BPM  {}",
+            clock(time),
+            clock(duration),
+            section.map_or(0, |i| i + 1),
+            analysis.sections.len(),
+            tempo,
+            key.unwrap_or_default()
+        ),
+        timeline(&sections, duration, time, WIDTH).dimmed().to_string(),
+        format!("{:>9} {} bar {}", "Beat", bar(strength, WIDTH).red(), bar_number),
+        format!("{:>9} {} {:.1} dB", "Loudness", bar(loudness_level(loudness), WIDTH).green(), loudness),
+        format!("{:>9} {}", "Envelope", envelope.cyan()),
+        format!("{:>9} {}", "", chroma.concat().yellow()),
+        format!(
+            "{:>9} {}",
+            "Chroma",
+            PITCH_CLASSES.iter().map(|pitch| format!("{:<3}", pitch)).collect::<String>()
+        ),
+    ]
+}
+
+/// Draw `lines` over the previous frame of `previous` lines.
+fn draw(lines: &[String], previous: usize) -> io::Result<()> {
+    let mut out = io::stdout();
+    if previous > 0 {
+        write!(out, "\x1b[{}A", previous)?;
+    }
+    for line in lines {
+        writeln!(out, "\x1b[2K{}", line)?;
+    }
+    out.flush()
+}
+
+/// Visualize the current track until interrupted with Ctrl-C.
+pub async fn visualize(config: Config) -> Result<(), Box<dyn Error>> {
+    print!("\x1b[?25l");
+    let result = tokio::select! {
+        result = run(&config) => result,
+        _ = tokio::signal::ctrl_c() => Ok(()),
+    };
+    println!("\x1b[?25h");
+    result
+}
+
+async fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    // Track ID, its title line and analysis.
+    let mut track: Option<(String, String, AudioAnalysis)> = None;
+    let mut drawn = 0;
+
+    loop {
+        let playing = currently_playing(config).await?;
+        let synced_at = Instant::now();
+
+        let (progress, is_playing) = match playing {
+            Some(playing) => match (playing.item, playing.progress) {
+                // Local files have no ID and no analysis.
+                (Some(PlayingItem::Track(item)), Some(progress)) if item.id.is_some() => {
+                    let id = item.id.clone().unwrap_or_default();
+                    if track.as_ref().map(|(current, _, _)| current) != Some(&id) {
+                        let url = format!("https://api.spotify.com/v1/audio-analysis/{}", id);
+                        let analysis: AudioAnalysis = get(config, &url).await?;
+                        let title = format!("{} {}", item.name.green().bold(), artist_names(&item.artists));
+                        track = Some((id, title, analysis));
+                    }
+                    (progress, playing.is_playing)
+                }
+                _ => {
+                    track = None;
+                    (Duration::default(), false)
+                }
+            },
+            None => {
+                track = None;
+                (Duration::default(), false)
+            }
+        };
+
+        while synced_at.elapsed() < SYNC_INTERVAL {
+            let mut lines = match &track {
+                Some((_, title, analysis)) => {
+                    let elapsed = if is_playing { synced_at.elapsed() } else { Duration::default() };
+                    // Progress often runs past the analysis near the end, hold
+                    // the last frame until the next sync instead of polling.
+                    let time = (progress + elapsed).as_secs_f32().min(analysis.track.duration);
+                    render(analysis, title, time)
+                }
+                None => vec!["Not playing a track.".to_string()],
+            };
+
+            // Clear what's left of a taller previous frame.
+            lines.resize(lines.len().max(drawn), String::new());
+            draw(&lines, drawn)?;
+            drawn = lines.len();
+
+            sleep(FRAME).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(start: f32, duration: f32) -> TimeInterval {
+        TimeInterval {
+            start,
+            duration,
+            confidence: 1.0,
+        }
+    }
+
+    #[test]
+    fn test_find_index() {
+        let beats = vec![interval(0.5, 0.5), interval(1.0, 0.5), interval(1.5, 0.5)];
+        let start = |beat: &TimeInterval| beat.start;
+        assert_eq!(find_index(&beats, 0.2, start), None);
+        assert_eq!(find_index(&beats, 1.0, start), Some(1));
+        assert_eq!(find_index(&beats, 9.0, start), Some(2));
+        assert!((pulse(&beats[1], 1.25) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_timeline() {
+        assert_eq!(timeline(&[0.0, 50.0], 100.0, 10.0, 10), "─●───┆────");
+        assert_eq!(level(0.0), '▁');
+        assert_eq!(level(1.0), '█');
+    }
+}
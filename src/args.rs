@@ -1,17 +1,63 @@
+use chrono::NaiveDate;
 use clap::{Clap};
 
+use std::path::PathBuf;
+
+use crate::api::{BrowseAction, ExportFormat, ExportSource, ImportTarget, LibraryKind, MatchStrategy, SortKey, TopKind};
+use crate::config::validate_profile;
+use crate::model::{ArtistId, EpisodeId, ItemId, PlaylistId, SearchType, TimeRange, TrackId};
+use crate::output::OutputFormat;
+
 #[derive(Clap)]
 #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
 pub struct Opts {
+    /// Run as this profile instead of the default one.
+    #[clap(long, global = true, parse(try_from_str = validate_profile))]
+    pub profile: Option<String>,
     #[clap(subcommand)]
     pub subcmd: SubCommand
 }
 
+/// `--play` and `--queue` of commands listing numbered rows.
+#[derive(Clap)]
+pub struct RowOpts {
+    /// Play the row with this number.
+    #[clap(long, conflicts_with = "queue")]
+    pub play: Option<usize>,
+    /// Add the row with this number to the queue.
+    #[clap(long)]
+    pub queue: Option<usize>,
+}
+
+impl RowOpts {
+    pub fn action(&self) -> BrowseAction {
+        match (self.play, self.queue) {
+            (Some(row), _) => BrowseAction::Play(row),
+            (_, Some(row)) => BrowseAction::Queue(row),
+            _ => BrowseAction::List,
+        }
+    }
+}
+
+/// `--format` of commands printing lists.
+#[derive(Clap)]
+pub struct FormatOpts {
+    /// Output format: text, json or csv.
+    #[clap(long, default_value = "text")]
+    pub format: OutputFormat,
+}
+
+// Parsed once per run, so the size of `Recommend` doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Clap)]
 pub enum SubCommand {
     /// Plays a specific song or resume latest.
     #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
-    Play,
+    Play {
+        /// Spotify URI or open.spotify.com URL of a track, episode, album,
+        /// artist, playlist or show.
+        item: Option<ItemId>,
+    },
     /// Pauses playback.
     #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
     Pause,
@@ -31,11 +77,553 @@ pub enum SubCommand {
     Current,
     #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
     /// Skip to the next song.
-    Skip,
+    Skip {
+        /// Jump this many seconds ahead instead, e.g. through podcast ads.
+        #[clap(long)]
+        seconds: Option<u64>,
+    },
     #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
     /// Skip to the previous song.
-    Back,
+    Back {
+        /// Jump this many seconds back instead.
+        #[clap(long)]
+        seconds: Option<u64>,
+    },
     #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
     /// Print out some shorthand aliases for common commands for Bash/ZSH
-    Alias
+    Alias,
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show the Spotify account for the current profile.
+    Whoami,
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Manage accounts/profiles.
+    Profile(ProfileCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// View and change settings.
+    Config(ConfigCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Search the Spotify catalog.
+    Search {
+        #[clap(required = true)]
+        query: Vec<String>,
+        /// What to search for: track, album, artist, playlist, show or episode.
+        #[clap(long = "type", short = 't', default_value = "track")]
+        kind: SearchType,
+        #[clap(long, default_value = "10")]
+        limit: u32,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Save the current track or episode to your library.
+    Like,
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Remove the current track or episode from your library.
+    Unlike,
+    #[clap(name = "liked?", version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Check whether the current track or episode is in your library, exits
+    /// with 1 if it isn't.
+    Liked,
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// List your saved tracks, albums or shows.
+    Library {
+        /// tracks, albums or shows
+        kind: LibraryKind,
+        /// Only list items saved on or after this date (YYYY-MM-DD).
+        #[clap(long)]
+        since: Option<NaiveDate>,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show an artist's top tracks, albums and related artists.
+    Artist {
+        /// Artist URI, URL or name.
+        #[clap(required = true)]
+        query: Vec<String>,
+        #[clap(flatten)]
+        row: RowOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show an album's tracks and copyrights.
+    Album {
+        /// Album URI, URL or name.
+        #[clap(required = true)]
+        query: Vec<String>,
+        #[clap(flatten)]
+        row: RowOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show a podcast's latest episodes and how far into them you are.
+    Show {
+        /// Show URI, URL or name.
+        #[clap(required = true)]
+        query: Vec<String>,
+        #[clap(long, default_value = "20")]
+        limit: usize,
+        #[clap(flatten)]
+        row: RowOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// List saved shows with episodes you haven't finished.
+    Podcasts {
+        /// Unplayed episodes listed per show.
+        #[clap(long, default_value = "3")]
+        limit: usize,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Play podcast episodes.
+    Episode(EpisodeCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// List recently played tracks.
+    History {
+        #[clap(subcommand)]
+        cmd: Option<HistoryCommand>,
+        #[clap(long, default_value = "50")]
+        limit: usize,
+        /// Only list plays before this date (YYYY-MM-DD).
+        #[clap(long)]
+        before: Option<NaiveDate>,
+        /// Only list plays on or after this date (YYYY-MM-DD).
+        #[clap(long)]
+        after: Option<NaiveDate>,
+        /// List plays from the local log, which goes back further than the
+        /// last 50 Spotify keeps.
+        #[clap(long)]
+        local: bool,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Report on your listening from the local history log, without
+    /// contacting Spotify.
+    Stats {
+        /// Only count plays on or after this date (YYYY-MM-DD).
+        #[clap(long)]
+        since: Option<NaiveDate>,
+        /// Only count plays on or before this date (YYYY-MM-DD).
+        #[clap(long)]
+        until: Option<NaiveDate>,
+        /// Tracks, artists, albums and genres listed.
+        #[clap(long, default_value = "10")]
+        top: usize,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// List your top tracks or artists, or what changed in them.
+    Top(TopCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// List, show and edit playlists.
+    Playlist(PlaylistCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Sync playlists defined by rule files.
+    Smart(SmartCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show tempo, key and other audio features of a track, the current one
+    /// if none is given.
+    Features { track: Option<TrackId> },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Show the sections of a track with their tempo and key changes, the
+    /// current track if none is given.
+    Analysis { track: Option<TrackId> },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Recommend tracks based on up to five seed artists, tracks and genres,
+    /// tuned by --min-*, --max-* and --target-* audio attributes.
+    Recommend {
+        /// Use the current track as a seed.
+        #[clap(long)]
+        seed_current: bool,
+        #[clap(long = "seed-artist")]
+        seed_artists: Vec<ArtistId>,
+        #[clap(long = "seed-track")]
+        seed_tracks: Vec<TrackId>,
+        #[clap(long = "seed-genre")]
+        seed_genres: Vec<String>,
+        #[clap(long, default_value = "20")]
+        limit: u32,
+        #[clap(flatten)]
+        tunables: Tunables,
+        /// Play the recommendations.
+        #[clap(long, conflicts_with_all = &["queue", "to-playlist"])]
+        play: bool,
+        /// Add the recommendations to the queue.
+        #[clap(long, conflicts_with = "to-playlist")]
+        queue: bool,
+        /// Add the recommendations to this playlist.
+        #[clap(long)]
+        to_playlist: Option<PlaylistId>,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Visualize beats, loudness and pitches of the current track live.
+    Visualize,
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Play endlessly, topping up the queue with recommendations seeded from
+    /// what was played recently.
+    Radio {
+        /// Track or artist to start from, defaults to the current track.
+        seed: Option<ItemId>,
+        /// Top up the queue when fewer tracks than this are left.
+        #[clap(long, default_value = "3")]
+        min_queue: usize,
+        /// Tracks added at a time.
+        #[clap(long, default_value = "10")]
+        batch: usize,
+        /// Tracks remembered to avoid repeats.
+        #[clap(long, default_value = "200")]
+        history: usize,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Pick and order tracks by key and tempo for DJ sets.
+    Mix(MixCommand),
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Export a playlist, album or your liked songs to a file.
+    Export {
+        /// liked, or a playlist or album URI/URL.
+        #[clap(required_unless_present = "all")]
+        source: Option<ExportSource>,
+        /// Export every playlist you own into a directory.
+        #[clap(long, conflicts_with = "source")]
+        all: bool,
+        /// m3u, xspf, csv or json
+        #[clap(long, default_value = "json")]
+        format: ExportFormat,
+        /// File to write, or directory with --all. Defaults to stdout, or
+        /// spotr-export-<date> with --all.
+        #[clap(long, short = 'o')]
+        output: Option<PathBuf>,
+    },
+    #[clap(version = "0.1", author = "Avery Wagar <ajmw.subs@gmail.com>")]
+    /// Import tracks from an M3U, CSV or JSON file into a playlist.
+    Import {
+        file: PathBuf,
+        /// new, or a playlist URI/URL to add the tracks to.
+        #[clap(long, default_value = "new")]
+        into: ImportTarget,
+        /// Name of the new playlist, defaults to the name in the file or the
+        /// file name.
+        #[clap(long)]
+        name: Option<String>,
+        /// Lowest confidence (0 to 1) accepted for fuzzy matches.
+        #[clap(long, default_value = "0.7")]
+        min_confidence: f64,
+        /// Only show how entries would be resolved.
+        #[clap(long)]
+        dry_run: bool,
+        /// Where to write unresolved entries, defaults to
+        /// <file>.unresolved.csv.
+        #[clap(long)]
+        report: Option<PathBuf>,
+    },
+}
+
+#[derive(Clap)]
+pub enum TopCommand {
+    /// List your top tracks.
+    Tracks {
+        #[clap(flatten)]
+        range: RangeOpts,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    /// List your top artists.
+    Artists {
+        #[clap(flatten)]
+        range: RangeOpts,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    /// Show climbers, fallers and new entries since the last diff.
+    Diff {
+        /// tracks or artists
+        #[clap(default_value = "tracks")]
+        kind: TopKind,
+        #[clap(flatten)]
+        range: RangeOpts,
+    },
+}
+
+/// Time range and length of top lists.
+#[derive(Clap)]
+pub struct RangeOpts {
+    /// short (4 weeks), medium (6 months) or long (all time).
+    #[clap(long, default_value = "medium")]
+    pub range: TimeRange,
+    #[clap(long, default_value = "20")]
+    pub limit: usize,
+}
+
+#[derive(Clap)]
+pub enum HistoryCommand {
+    /// Add recently played tracks to the local log. Run it at least every
+    /// 50 tracks to keep the log complete.
+    Sync,
+}
+
+#[derive(Clap)]
+pub enum EpisodeCommand {
+    /// Play an episode from where you left off, by default the newest one
+    /// you started in your saved shows.
+    Resume { episode: Option<EpisodeId> },
+}
+
+#[derive(Clap)]
+pub enum ConfigCommand {
+    /// Print a setting: playlist, device, default_profile, token_storage,
+    /// client_id or client_secret.
+    Get { key: String },
+    /// Change a setting. playlist and device are set on the current profile.
+    Set { key: String, value: String },
+    /// Remove a setting.
+    Unset { key: String },
+    /// Print the path of the config file.
+    Path,
+    /// Open the config file in $EDITOR.
+    Edit,
+    /// Show all settings in effect, including environment overrides.
+    Show,
+}
+
+impl SubCommand {
+    /// Whether the command needs a logged in user, rather than just access to
+    /// the public catalog.
+    pub fn requires_user(&self) -> bool {
+        !matches!(
+            self,
+            SubCommand::Search { .. }
+                | SubCommand::Config(_)
+                | SubCommand::Recommend { seed_current: false, play: false, queue: false, to_playlist: None, .. }
+                | SubCommand::Features { track: Some(_) }
+                | SubCommand::Analysis { track: Some(_) }
+                | SubCommand::Playlist(PlaylistCommand::Show { .. })
+                | SubCommand::Artist { row: RowOpts { play: None, queue: None }, .. }
+                | SubCommand::Album { row: RowOpts { play: None, queue: None }, .. }
+        )
+    }
+}
+
+/// Defines `Tunables`, with `--min-*`, `--max-*` and `--target-*` options for
+/// every attribute recommendations can be tuned by.
+macro_rules! tunables {
+    ($($min:ident $max:ident $target:ident),* $(,)?) => {
+        #[derive(Clap)]
+        pub struct Tunables {
+            $(
+                #[clap(long)]
+                pub $min: Option<f64>,
+                #[clap(long)]
+                pub $max: Option<f64>,
+                #[clap(long)]
+                pub $target: Option<f64>,
+            )*
+        }
+
+        impl Tunables {
+            /// Query parameters of the options given, e.g. `min_tempo`.
+            pub fn params(&self) -> Vec<(String, f64)> {
+                let mut params = Vec::new();
+                $(
+                    for (name, value) in &[
+                        (stringify!($min), self.$min),
+                        (stringify!($max), self.$max),
+                        (stringify!($target), self.$target),
+                    ] {
+                        if let Some(value) = value {
+                            params.push((name.to_string(), *value));
+                        }
+                    }
+                )*
+                params
+            }
+        }
+    };
+}
+
+tunables!(
+    min_acousticness max_acousticness target_acousticness,
+    min_danceability max_danceability target_danceability,
+    min_duration_ms max_duration_ms target_duration_ms,
+    min_energy max_energy target_energy,
+    min_instrumentalness max_instrumentalness target_instrumentalness,
+    min_key max_key target_key,
+    min_liveness max_liveness target_liveness,
+    min_loudness max_loudness target_loudness,
+    min_mode max_mode target_mode,
+    min_popularity max_popularity target_popularity,
+    min_speechiness max_speechiness target_speechiness,
+    min_tempo max_tempo target_tempo,
+    min_time_signature max_time_signature target_time_signature,
+    min_valence max_valence target_valence,
+);
+
+#[derive(Clap)]
+pub enum ProfileCommand {
+    /// List all profiles.
+    List,
+    /// Add a new profile, use sp --profile <name> login to log into it.
+    Add {
+        #[clap(parse(try_from_str = validate_profile))]
+        name: String,
+    },
+    /// Remove a profile and its tokens.
+    Remove { name: String },
+    /// Set the profile used when --profile isn't given.
+    Default { name: String },
+}
+
+#[derive(Clap)]
+pub enum MixCommand {
+    /// Suggest tracks from a playlist that mix well after the current track,
+    /// from the default playlist if none is given.
+    Next {
+        playlist: Option<PlaylistId>,
+        /// Largest tempo change in percent.
+        #[clap(long, default_value = "6")]
+        bpm: f32,
+        #[clap(long, default_value = "10")]
+        limit: usize,
+    },
+    /// Order a playlist to keep key and tempo jumps small.
+    Order {
+        playlist: PlaylistId,
+        /// Reorder the playlist instead of only showing the order.
+        #[clap(long)]
+        apply: bool,
+    },
+}
+
+#[derive(Clap)]
+pub enum SmartCommand {
+    /// List the rules in the rules directory.
+    List,
+    /// Write the tracks a rule selects into its playlist, created on the first
+    /// sync.
+    Sync {
+        /// Name of a rule in the rules directory, or the path of a rule file.
+        rule: String,
+        /// Only show what would change.
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Clap)]
+pub enum PlaylistCommand {
+    /// List your playlists.
+    List {
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    /// List the tracks of a playlist, the default playlist if none is given.
+    Show {
+        playlist: Option<PlaylistId>,
+        #[clap(flatten)]
+        output: FormatOpts,
+    },
+    /// Create a new playlist.
+    Create {
+        name: String,
+        #[clap(long)]
+        description: Option<String>,
+        /// Show the playlist on your profile.
+        #[clap(long)]
+        public: bool,
+        /// Let others edit the playlist.
+        #[clap(long)]
+        collaborative: bool,
+    },
+    /// Rename a playlist.
+    Rename { playlist: PlaylistId, name: String },
+    /// Change the description of a playlist.
+    Describe { playlist: PlaylistId, description: String },
+    /// Add tracks or episodes to a playlist.
+    Add {
+        playlist: PlaylistId,
+        #[clap(required = true)]
+        items: Vec<ItemId>,
+        /// Insert at this position (starting at 1) instead of the end.
+        #[clap(long)]
+        position: Option<u32>,
+    },
+    /// Remove tracks or episodes from a playlist.
+    Remove {
+        playlist: PlaylistId,
+        #[clap(required = true)]
+        items: Vec<ItemId>,
+        /// Fail if the playlist no longer has this snapshot ID.
+        #[clap(long)]
+        snapshot: Option<String>,
+    },
+    /// Move items within a playlist, positions as listed by sp playlist show.
+    Reorder {
+        playlist: PlaylistId,
+        from: u32,
+        /// Position to move the items before.
+        to: u32,
+        /// Number of items to move.
+        #[clap(long, default_value = "1")]
+        length: u32,
+        /// Fail if the playlist no longer has this snapshot ID.
+        #[clap(long)]
+        snapshot: Option<String>,
+    },
+    /// Follow a playlist.
+    Follow { playlist: PlaylistId },
+    /// Unfollow a playlist, this is how your own playlists are deleted.
+    Unfollow { playlist: PlaylistId },
+    /// Store the current items of a playlist locally.
+    Snapshot { playlist: PlaylistId },
+    /// List the stored snapshots of a playlist.
+    History { playlist: PlaylistId },
+    /// Show tracks added, removed and moved between two snapshots.
+    Diff {
+        playlist: PlaylistId,
+        /// Snapshot ID (or prefix) to compare from, defaults to the latest
+        /// stored snapshot.
+        #[clap(long)]
+        from: Option<String>,
+        /// Snapshot ID (or prefix) to compare to, defaults to the playlist as
+        /// it is now.
+        #[clap(long)]
+        to: Option<String>,
+    },
+    /// Replace the items of a playlist with a stored snapshot.
+    Restore { playlist: PlaylistId, snapshot: String },
+    /// Remove tracks that are already earlier in a playlist.
+    Dedupe {
+        playlist: PlaylistId,
+        /// How duplicates are recognized: uri, isrc and/or title (same
+        /// normalized title and artist, durations within 3 seconds).
+        #[clap(long = "match", default_value = "uri,isrc", use_delimiter = true)]
+        strategies: Vec<MatchStrategy>,
+        /// Only report what would be removed.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Remove tracks that can no longer be played in your country.
+    Prune {
+        playlist: PlaylistId,
+        /// Only report what would be removed.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Sort a playlist, keeping when each track was added.
+    Sort {
+        playlist: PlaylistId,
+        /// added_at, artist, album, release_date, popularity, tempo, energy or
+        /// key.
+        #[clap(long)]
+        by: SortKey,
+        /// Sort in descending order.
+        #[clap(long)]
+        reverse: bool,
+    },
+    /// Shuffle a playlist, keeping when each track was added.
+    Shuffle {
+        playlist: PlaylistId,
+        /// Seed giving the same order every time, a random one is printed if
+        /// not given.
+        #[clap(long)]
+        seed: Option<u64>,
+    },
 }
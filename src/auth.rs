@@ -1,5 +1,21 @@
 const SPOTIFY_CLIENT_ID: &str = "f3a1096e3b9d43498c0a91fb713b65f1";
-const SPOTIFY_SCOPES: &[&str] = &["user-read-private", "user-read-email"];
+const SPOTIFY_SCOPES: &[&str] = &[
+    "app-remote-control",
+    "user-modify-playback-state",
+    "user-read-private",
+    "user-read-email",
+    "user-read-currently-playing",
+    "user-read-playback-state",
+    "user-read-playback-position",
+    "user-read-recently-played",
+    "user-top-read",
+    "user-library-read",
+    "user-library-modify",
+    "playlist-read-private",
+    "playlist-read-collaborative",
+    "playlist-modify-public",
+    "playlist-modify-private",
+];
 const SPOTIFY_REDIRECT_URL: &str = "http://localhost:8080";
 
 const SPOTIFY_AUTH_URL: &str = "https://accounts.spotify.com/authorize";
@@ -18,7 +34,10 @@ use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpListener;
 use url::Url;
 
-use crate::config::{load_config, save_config, AuthTokens, Config};
+use crate::config::{
+    load_config, load_config_file, save_config_file, AuthTokens, Config, ConfigFile, TokenStorage,
+};
+use crate::store::{self, TokenStore};
 
 fn get_oauth_client() -> Result<BasicClient, Box<dyn Error>> {
     // Create an OAuth2 client by specifying the client ID, client secret, authorization URL and
@@ -35,7 +54,7 @@ fn get_oauth_client() -> Result<BasicClient, Box<dyn Error>> {
     Ok(client)
 }
 
-pub async fn login() -> Result<(), Box<dyn Error>> {
+pub async fn login(profile: &str) -> Result<(), Box<dyn Error>> {
     println!("{}", "Starting OAuth2.0 PKCE Flow".italic());
     let client = get_oauth_client()?;
 
@@ -43,20 +62,17 @@ pub async fn login() -> Result<(), Box<dyn Error>> {
     let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
 
     // Generate the full authorization URL.
-    let (auth_url, csrf_state) = client
+    let mut auth_request = client
         .authorize_url(CsrfToken::new_random)
-        // Set the desired scopes.
-        // .add_scope(Scope::new("user-read-recently-played".to_string()))
-        // .add_scope(Scope::new("user-read-playback-state".to_string()))
-        // .add_scope(Scope::new("playlist-modify-private".to_string()))
-        // .add_scope(Scope::new("playlist-read-private".to_string()))
-        .add_scope(Scope::new("app-remote-control".to_string()))
-        .add_scope(Scope::new("user-modify-playback-state".to_string()))
-        // .add_scope(Scope::new("streaming".to_string()))
-        // .add_scope(Scope::new("user-top-read".to_string()))
         // Set the PKCE code challenge.
-        .set_pkce_challenge(pkce_challenge)
-        .url();
+        .set_pkce_challenge(pkce_challenge);
+
+    // Set the desired scopes.
+    for scope in SPOTIFY_SCOPES {
+        auth_request = auth_request.add_scope(Scope::new(scope.to_string()));
+    }
+
+    let (auth_url, csrf_state) = auth_request.url();
 
     // Open URL in browser
     match open::that(auth_url.to_string()) {
@@ -123,18 +139,17 @@ pub async fn login() -> Result<(), Box<dyn Error>> {
 
                 let (access_token, refresh_token) = get_access_token(&code, &PkceCodeVerifier::new(pkce_verify_string.clone())).await?;
 
-                // Save token to config
-                let config = Config {
-                    auth: AuthTokens {
-                        refresh_token: Some(refresh_token.secret().clone()),
-                        access_token: Some(access_token.secret().clone()),
-                    },
-                    ..load_config()?
+                // Save token to the configured token store
+                let config = load_config(Some(profile))?;
+                let mut file = load_config_file()?;
+                let tokens = AuthTokens {
+                    refresh_token: Some(refresh_token.secret().clone()),
+                    access_token: Some(access_token.secret().clone()),
                 };
+                save_login(&mut file, store::open(&config)?.as_ref(), profile, tokens)?;
+                save_config_file(file)?;
 
-                save_config(config)?;
-
-                println!("{}", "Logged in!".green().bold());
+                println!("{} as profile {}.", "Logged in".green().bold(), profile.bold());
                 // The server will terminate itself after collecting the first code.
                 break;
             }
@@ -144,10 +159,29 @@ pub async fn login() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-pub async fn refresh_token() -> Result<Config, Box<dyn Error>> {
-    let config = load_config()?;
+/// Keep the `tokens` of a fresh login to `profile`, adding the profile to
+/// `file` whichever store the tokens go to. Plaintext tokens are written into
+/// `file`, which the caller saves.
+fn save_login(
+    file: &mut ConfigFile,
+    store: &dyn TokenStore,
+    profile: &str,
+    tokens: AuthTokens,
+) -> Result<(), Box<dyn Error>> {
+    let plaintext = file.token_storage == TokenStorage::Plaintext;
+    let entry = file.profiles.entry(profile.to_string()).or_default();
+    if plaintext {
+        entry.auth = tokens;
+    } else {
+        store.save(&tokens)?;
+    }
+    Ok(())
+}
 
-    if let Some(refresh_str) = config.auth.refresh_token {
+/// Trade the refresh token in `config.auth`, as loaded from `store`, for new
+/// tokens and save them back to `store`.
+pub async fn refresh_token(config: Config, store: &dyn TokenStore) -> Result<Config, Box<dyn Error>> {
+    if let Some(refresh_str) = config.auth.refresh_token.clone() {
         let http = reqwest::Client::new();
         let mut params = HashMap::new();
         
@@ -158,22 +192,50 @@ pub async fn refresh_token() -> Result<Config, Box<dyn Error>> {
         let resp = http.post(SPOTIFY_TOKEN_URL).form(&params).send().await?;
         let tokens: Tokens = resp.json().await?;
 
-        let config = Config {
-            auth: AuthTokens {
-                refresh_token: Some(tokens.refresh_token),
-                access_token: Some(tokens.access_token),
-            },
-            ..config
+        let auth = AuthTokens {
+            refresh_token: Some(tokens.refresh_token),
+            access_token: Some(tokens.access_token),
         };
 
-        save_config(config.clone())?;
+        store.save(&auth)?;
 
-        Ok(config)
+        Ok(Config { auth, ..config })
     } else {
         Err(String::from("No refresh token in config. Please login using sp login.").into())
     }
 }
 
+/// Get an app-only access token using the client credentials grant. The
+/// token can only be used for catalog endpoints, never for user data.
+pub async fn client_credentials_token(config: Config) -> Result<Config, Box<dyn Error>> {
+    let (client_id, client_secret) = match (&config.client_id, &config.client_secret) {
+        (Some(id), Some(secret)) => (id.clone(), secret.clone()),
+        _ => return Err(String::from("No client_id/client_secret in config. Please login using sp login.").into()),
+    };
+
+    let http = reqwest::Client::new();
+    let mut params = HashMap::new();
+
+    params.insert("grant_type", "client_credentials".to_string());
+
+    let resp = http
+        .post(SPOTIFY_TOKEN_URL)
+        .basic_auth(client_id, Some(client_secret))
+        .form(&params)
+        .send()
+        .await?
+        .error_for_status()?;
+    let tokens: ClientTokens = resp.json().await?;
+
+    Ok(Config {
+        auth: AuthTokens {
+            refresh_token: None,
+            access_token: Some(tokens.access_token),
+        },
+        ..config
+    })
+}
+
 pub async fn get_access_token(code: &AuthorizationCode, code_verifier: &PkceCodeVerifier) -> Result<(AccessToken, RefreshToken), Box<dyn Error>> {
     let http = reqwest::Client::new();
 
@@ -198,24 +260,60 @@ struct Tokens {
     pub expires_in: usize,
 }
 
+#[derive(Serialize, Deserialize)]
+struct ClientTokens {
+    pub access_token: String,
+    pub expires_in: usize,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::DEFAULT_PROFILE;
 
     #[tokio::test]
     async fn test_refresh() {
-        let result = refresh_token().await;
+        let mut config = load_config(Some(DEFAULT_PROFILE)).unwrap();
+        let store = store::open(&config).unwrap();
+        config.auth = store::load_tokens(&config, store.as_ref()).unwrap();
+        let result = refresh_token(config, store.as_ref()).await;
         println!("Result: {:?}", result);
         assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_login() {
-        let result = login().await;
+        let result = login(DEFAULT_PROFILE).await;
         println!("Result: {:?}", result);
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_save_login_adds_profile() {
+        let tokens = AuthTokens {
+            refresh_token: Some("refresh".to_string()),
+            access_token: Some("access".to_string()),
+        };
+        let dir = store::test_dir("login");
+        let store = store::EncryptedFileStore::with_passphrase(dir.join("tokens.enc"), "hunter2");
+
+        let mut file = ConfigFile {
+            token_storage: TokenStorage::EncryptedFile,
+            ..Default::default()
+        };
+        save_login(&mut file, &store, "work", tokens.clone()).unwrap();
+        assert!(file.has_profile("work"));
+        assert!(file.profiles["work"].auth.is_empty());
+        assert_eq!(store.load().unwrap(), tokens);
+        store.clear().unwrap();
+
+        let mut file = ConfigFile::default();
+        save_login(&mut file, &store, "work", tokens.clone()).unwrap();
+        assert_eq!(file.profiles["work"].auth, tokens);
+        assert!(store.load().unwrap().is_empty());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     #[tokio::test]
     async fn test_get_oauth_client() {
         let res = get_oauth_client();
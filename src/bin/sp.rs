@@ -1,45 +1,238 @@
-use std::error::Error;
-
 use clap::Clap;
-use spotr::{api::{alias, back, current, pause, play, skip}, args::{Opts, SubCommand}, auth::login, config::load_config};
+use spotr::{api::{self, alias, back, current, library, like, liked, pause, play, search, skip, unlike, whoami}, args::{ConfigCommand, EpisodeCommand, HistoryCommand, MixCommand, Opts, PlaylistCommand, ProfileCommand, SmartCommand, SubCommand, TopCommand}, auth::{client_credentials_token, login}, config::{self, load_config, load_config_file}, profile, settings, store::{self, load_tokens}};
 
 #[tokio::main]
 async fn main() {
     let opts: Opts = Opts::parse();
 
-    let mut config = match load_config() {
-        Ok(config) => config,
+    // These have to work even if the config file is broken.
+    if let SubCommand::Config(cmd @ (ConfigCommand::Path | ConfigCommand::Edit)) = &opts.subcmd {
+        if let Err(result) = match cmd {
+            ConfigCommand::Path => settings::path(),
+            _ => settings::edit(),
+        } {
+            eprintln!("Error occured: {}", result);
+        }
+        return;
+    }
+
+    let file = match load_config_file() {
+        Ok(file) => file,
         Err(why) => {
-            eprintln!("Cannot find config file: {:?}", why);
+            eprintln!("{}", why);
+            return;
+        }
+    };
+
+    // --profile is checked while parsing, SPOTR_PROFILE and the config file here.
+    let profile = match config::validate_profile(&opts.profile.unwrap_or_else(|| file.default_profile())) {
+        Ok(profile) => profile,
+        Err(why) => {
+            eprintln!("{}", why);
             return;
         }
     };
 
     match opts.subcmd {
+        SubCommand::Profile(cmd) => {
+            if let Err(result) = match cmd {
+                ProfileCommand::List => profile::list(),
+                ProfileCommand::Add { name } => profile::add(&name),
+                ProfileCommand::Remove { name } => profile::remove(&name),
+                ProfileCommand::Default { name } => profile::set_default(&name),
+            } {
+                eprintln!("Error occured: {}", result);
+            }
+        }
+        SubCommand::Config(cmd) => {
+            if let Err(result) = match cmd {
+                ConfigCommand::Get { key } => settings::get(&profile, &key),
+                ConfigCommand::Set { key, value } => settings::set(&profile, &key, &value),
+                ConfigCommand::Unset { key } => settings::unset(&profile, &key),
+                ConfigCommand::Show => settings::show(&profile),
+                ConfigCommand::Path | ConfigCommand::Edit => Ok(()),
+            } {
+                eprintln!("Error occured: {}", result);
+            }
+        }
+        SubCommand::Smart(SmartCommand::List) => {
+            if let Err(result) = api::list_smart() {
+                eprintln!("Error occured: {}", result);
+            }
+        }
+        SubCommand::Stats { since, until, top, output } => {
+            if let Err(result) = api::stats(&profile, since, until, top, output.format) {
+                eprintln!("Error occured: {}", result);
+            }
+        }
         SubCommand::Login => {
-            let _ = login().await;
+            let _ = login(&profile).await;
         }
         subcmd => {
+            if subcmd.requires_user() && !file.has_profile(&profile) {
+                eprintln!("No profile named {}, please use sp --profile {} login.", profile, profile);
+                return;
+            }
+
+            let mut config = match load_config(Some(&profile)) {
+                Ok(config) => config,
+                Err(why) => {
+                    eprintln!("{}", why);
+                    return;
+                }
+            };
+
+            // Opened once, so an encrypted file only asks for its passphrase once.
+            let store = match store::open(&config) {
+                Ok(store) => store,
+                Err(why) => {
+                    eprintln!("Failed to open {:?} token storage: {}", config.token_storage, why);
+                    return;
+                }
+            };
+            config.auth = match load_tokens(&config, store.as_ref()) {
+                Ok(auth) => auth,
+                Err(why) => {
+                    eprintln!("Failed to load tokens from {:?} storage: {}", config.token_storage, why);
+                    return;
+                }
+            };
+
             if let Some(_refresh_token_str) = &config.auth.refresh_token {
-                config = match spotr::auth::refresh_token().await {
+                config = match spotr::auth::refresh_token(config, store.as_ref()).await {
                     Ok(config) => config,
                     Err(why) => {
                         eprintln!("Failed to refresh access token from Spotify API: {}.", why);
                         return;
                     }
                 };
+            } else if !subcmd.requires_user() && config.client_secret.is_some() {
+                config = match client_credentials_token(config).await {
+                    Ok(config) => config,
+                    Err(why) => {
+                        eprintln!("Failed to get a client credentials token from Spotify API: {}.", why);
+                        return;
+                    }
+                };
             } else {
                 eprintln!("You're not logged in, please use sp login.");
                 return;
             }
 
             if let Err(result) = match subcmd {
-                SubCommand::Play => play(config).await,
+                SubCommand::Play { item } => play(config, item).await,
                 SubCommand::Pause => pause(config).await,
                 SubCommand::Current => current(config).await,
-                SubCommand::Skip => skip(config).await,
-                SubCommand::Back => back(config).await,
+                SubCommand::Skip { seconds } => skip(config, seconds).await,
+                SubCommand::Back { seconds } => back(config, seconds).await,
                 SubCommand::Alias => alias(config).await,
+                SubCommand::Whoami => whoami(config).await,
+                SubCommand::Search { query, kind, limit, output } => {
+                    search(config, query.join(" "), kind, limit, output.format).await
+                }
+                SubCommand::Like => like(config).await,
+                SubCommand::Unlike => unlike(config).await,
+                SubCommand::Liked => match liked(config).await {
+                    Ok(true) => Ok(()),
+                    Ok(false) => std::process::exit(1),
+                    Err(why) => {
+                        eprintln!("Error occured: {}", why);
+                        std::process::exit(2);
+                    }
+                },
+                SubCommand::Library { kind, since, output } => library(config, kind, since, output.format).await,
+                SubCommand::Playlist(cmd) => match cmd {
+                    PlaylistCommand::List { output } => api::list_playlists(config, output.format).await,
+                    PlaylistCommand::Show { playlist, output } => api::show_playlist(config, playlist, output.format).await,
+                    PlaylistCommand::Create { name, description, public, collaborative } => {
+                        api::create_playlist(config, name, description, public, collaborative).await
+                    }
+                    PlaylistCommand::Rename { playlist, name } => api::rename_playlist(config, playlist, name).await,
+                    PlaylistCommand::Describe { playlist, description } => {
+                        api::describe_playlist(config, playlist, description).await
+                    }
+                    PlaylistCommand::Add { playlist, items, position } => {
+                        api::add_to_playlist(config, playlist, items, position).await
+                    }
+                    PlaylistCommand::Remove { playlist, items, snapshot } => {
+                        api::remove_from_playlist(config, playlist, items, snapshot).await
+                    }
+                    PlaylistCommand::Reorder { playlist, from, to, length, snapshot } => {
+                        api::reorder_playlist(config, playlist, from, to, length, snapshot).await
+                    }
+                    PlaylistCommand::Follow { playlist } => api::follow_playlist(config, playlist).await,
+                    PlaylistCommand::Unfollow { playlist } => api::unfollow_playlist(config, playlist).await,
+                    PlaylistCommand::Snapshot { playlist } => api::snapshot_playlist(config, playlist).await,
+                    PlaylistCommand::History { playlist } => api::playlist_history(config, playlist).await,
+                    PlaylistCommand::Diff { playlist, from, to } => api::diff_playlist(config, playlist, from, to).await,
+                    PlaylistCommand::Restore { playlist, snapshot } => {
+                        api::restore_playlist(config, playlist, snapshot).await
+                    }
+                    PlaylistCommand::Dedupe { playlist, strategies, dry_run } => {
+                        api::dedupe_playlist(config, playlist, strategies, dry_run).await
+                    }
+                    PlaylistCommand::Prune { playlist, dry_run } => api::prune_playlist(config, playlist, dry_run).await,
+                    PlaylistCommand::Sort { playlist, by, reverse } => api::sort_playlist(config, playlist, by, reverse).await,
+                    PlaylistCommand::Shuffle { playlist, seed } => api::shuffle_playlist(config, playlist, seed).await,
+                },
+                SubCommand::Features { track } => api::features(config, track).await,
+                SubCommand::Analysis { track } => api::analysis(config, track).await,
+                SubCommand::Recommend {
+                    seed_current,
+                    seed_artists,
+                    seed_tracks,
+                    seed_genres,
+                    limit,
+                    tunables,
+                    play,
+                    queue,
+                    to_playlist,
+                } => {
+                    let seeds = api::Seeds { artists: seed_artists, tracks: seed_tracks, genres: seed_genres };
+                    let action = match (play, queue, to_playlist) {
+                        (true, _, _) => api::RecommendAction::Play,
+                        (_, true, _) => api::RecommendAction::Queue,
+                        (_, _, Some(playlist)) => api::RecommendAction::ToPlaylist(playlist),
+                        _ => api::RecommendAction::List,
+                    };
+                    api::recommend(config, seeds, seed_current, tunables.params(), limit, action).await
+                }
+                SubCommand::Artist { query, row } => api::artist(config, query.join(" "), row.action()).await,
+                SubCommand::Album { query, row } => api::album(config, query.join(" "), row.action()).await,
+                SubCommand::Show { query, limit, row } => api::show(config, query.join(" "), limit, row.action()).await,
+                SubCommand::History { cmd: Some(HistoryCommand::Sync), .. } => api::history_sync(config).await,
+                SubCommand::History { limit, before, after, local, output, .. } => {
+                    api::history(config, limit, before, after, local, output.format).await
+                }
+                SubCommand::Top(TopCommand::Tracks { range, output }) => {
+                    api::top(config, api::TopKind::Tracks, range.range, range.limit, output.format).await
+                }
+                SubCommand::Top(TopCommand::Artists { range, output }) => {
+                    api::top(config, api::TopKind::Artists, range.range, range.limit, output.format).await
+                }
+                SubCommand::Top(TopCommand::Diff { kind, range }) => {
+                    api::top_diff(config, kind, range.range, range.limit).await
+                }
+                SubCommand::Podcasts { limit } => api::podcasts(config, limit).await,
+                SubCommand::Episode(EpisodeCommand::Resume { episode }) => api::resume_episode(config, episode).await,
+                SubCommand::Visualize => api::visualize(config).await,
+                SubCommand::Radio { seed, min_queue, batch, history } => {
+                    api::radio(config, seed, min_queue, batch, history).await
+                }
+                SubCommand::Mix(MixCommand::Next { playlist, bpm, limit }) => {
+                    api::mix_next(config, playlist, bpm, limit).await
+                }
+                SubCommand::Mix(MixCommand::Order { playlist, apply }) => {
+                    api::mix_order_playlist(config, playlist, apply).await
+                }
+                SubCommand::Smart(SmartCommand::Sync { rule, dry_run }) => api::sync_smart(config, rule, dry_run).await,
+                SubCommand::Export { source: Some(source), format, output, .. } => {
+                    api::export(config, source, format, output).await
+                }
+                SubCommand::Export { format, output, .. } => api::export_all(config, format, output).await,
+                SubCommand::Import { file, into, name, min_confidence, dry_run, report } => {
+                    api::import(config, file, into, name, min_confidence, dry_run, report).await
+                }
                 _ => Ok(()),
             } {
                 eprintln!("Error occured: {}", result);
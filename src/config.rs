@@ -1,14 +1,67 @@
+use std::{collections::BTreeMap, env, error::Error, fs, io::ErrorKind, path::PathBuf};
+
+use directories::ProjectDirs;
 use serde::{Serialize, Deserialize};
+use toml::value::{Table, Value};
+
+use crate::model::PlaylistId;
 
 const CRATE_NAME: &str = "spotr";
 
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Version of the config file layout written by this build. Bump it whenever a
+/// migration is added to `MIGRATIONS`.
+pub const CONFIG_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` config file to version `n + 2`.
+/// Files without a `version` are version 1.
+const MIGRATIONS: &[fn(&mut Table)] = &[migrate_v1_to_v2];
+
+/// Environment variables that override settings of the active profile.
+pub const ENV_PROFILE: &str = "SPOTR_PROFILE";
+pub const ENV_DEVICE: &str = "SPOTR_DEVICE";
+pub const ENV_PLAYLIST: &str = "SPOTR_PLAYLIST";
+pub const ENV_CLIENT_ID: &str = "SPOTR_CLIENT_ID";
+pub const ENV_CLIENT_SECRET: &str = "SPOTR_CLIENT_SECRET";
+
+/// The config file as stored on disk, holding every profile.
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
-pub struct Config {
+#[serde(default)]
+pub struct ConfigFile {
+    pub version: u32,
+    /// Profile used when `--profile` isn't given.
+    pub default_profile: Option<String>,
+    /// Where `auth` tokens are kept. Anything other than `plaintext` leaves
+    /// `auth` empty in the config file.
+    pub token_storage: TokenStorage,
+    /// Credentials of your own Spotify app, used for catalog lookups without
+    /// a user login.
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+#[serde(default)]
+pub struct Profile {
     pub auth: AuthTokens,
     pub defaults: Defaults
 }
 
-#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+/// Settings for the profile a command runs as, with environment overrides
+/// applied.
+#[derive(Default, Debug, Clone)]
+pub struct Config {
+    pub profile: String,
+    pub auth: AuthTokens,
+    pub defaults: Defaults,
+    pub token_storage: TokenStorage,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq)]
 pub struct AuthTokens {
     pub refresh_token: Option<String>,
     pub access_token: Option<String>
@@ -16,18 +69,274 @@ pub struct AuthTokens {
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 pub struct Defaults {
+    /// Kept as written and parsed by `Config::default_playlist`, so a bad
+    /// value doesn't stop commands that don't use it.
     pub playlist: Option<String>,
     pub device: Option<String>
 }
 
+/// Token storage backend: `plaintext`, `secret_service` or `encrypted_file`.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStorage {
+    /// Stored in the config file alongside everything else.
+    #[default]
+    Plaintext,
+    /// Stored in the freedesktop Secret Service (gnome-keyring, KWallet, ...).
+    SecretService,
+    /// Stored in a separate file encrypted with a passphrase.
+    EncryptedFile,
+}
+
+impl AuthTokens {
+    pub fn is_empty(&self) -> bool {
+        self.refresh_token.is_none() && self.access_token.is_none()
+    }
+}
+
+impl ConfigFile {
+    /// Name of the profile to use when none is given explicitly.
+    pub fn default_profile(&self) -> String {
+        env::var(ENV_PROFILE)
+            .ok()
+            .or_else(|| self.default_profile.clone())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string())
+    }
+
+    pub fn has_profile(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+}
+
+impl Config {
+    /// The default playlist of the profile, if one is set.
+    pub fn default_playlist(&self) -> Result<Option<PlaylistId>, Box<dyn Error>> {
+        match &self.defaults.playlist {
+            Some(playlist) => playlist.parse().map(Some).map_err(|why| {
+                format!("playlist of profile {} in the config file: {}", self.profile, why).into()
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Directory holding the config file and any other files SpotR keeps.
+pub fn config_dir() -> Option<PathBuf> {
+    ProjectDirs::from("rs", "", CRATE_NAME).map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// Directory for data SpotR keeps besides its settings, like playlist
+/// snapshots.
+pub fn data_dir() -> Option<PathBuf> {
+    ProjectDirs::from("rs", "", CRATE_NAME).map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Path of the config file.
+pub fn config_path() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = config_dir().ok_or("Cannot find the config directory.")?;
+    Ok(dir.join(format!("{}.toml", CRATE_NAME)))
+}
+
+/// Load the config file, upgrading it to `CONFIG_VERSION` if it was written
+/// by an older version of SpotR.
+pub fn load_config_file() -> Result<ConfigFile, Box<dyn Error>> {
+    let path = config_path()?;
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(why) if why.kind() == ErrorKind::NotFound => {
+            return Ok(ConfigFile {
+                version: CONFIG_VERSION,
+                ..Default::default()
+            })
+        }
+        Err(why) => return Err(format!("Cannot read {}: {}", path.display(), why).into()),
+    };
+
+    let invalid = |why: &dyn std::fmt::Display| {
+        format!(
+            "Config file {} is invalid: {}. Fix it with sp config edit.",
+            path.display(),
+            why
+        )
+    };
 
-pub fn load_config() -> Result<Config, confy::ConfyError> {
-    let config: Config = confy::load(CRATE_NAME)?;
-    Ok(config)
+    let table: Table = toml::from_str(&contents).map_err(|why| invalid(&why))?;
+    let (table, migrated) = migrate(table)?;
+    let file: ConfigFile = Value::Table(table).try_into().map_err(|why| invalid(&why))?;
+
+    if migrated {
+        save_config_file(file.clone())?;
+    }
+
+    Ok(file)
 }
 
-pub fn save_config(config: Config) -> Result<(), confy::ConfyError> {
-    confy::store(CRATE_NAME, config)?;
+pub fn save_config_file(file: ConfigFile) -> Result<(), Box<dyn Error>> {
+    let path = config_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    confy::store_path(path, ConfigFile {
+        version: CONFIG_VERSION,
+        ..file
+    })?;
 
     Ok(())
 }
+
+/// Load the settings for `profile`, or the default profile if `None`.
+pub fn load_config(profile: Option<&str>) -> Result<Config, Box<dyn Error>> {
+    let file = load_config_file()?;
+    let name = profile.map(String::from).unwrap_or_else(|| file.default_profile());
+    let Profile { auth, mut defaults } = file.profiles.get(&name).cloned().unwrap_or_default();
+
+    // The file may have been edited by hand.
+    if let Some(device) = &defaults.device {
+        validate_device(device).map_err(|why| format!("device of profile {} in the config file: {}", name, why))?;
+    }
+    if let Ok(device) = env::var(ENV_DEVICE) {
+        defaults.device = Some(validate_device(&device).map_err(|why| format!("{}: {}", ENV_DEVICE, why))?);
+    }
+    if let Ok(playlist) = env::var(ENV_PLAYLIST) {
+        playlist.parse::<PlaylistId>().map_err(|why| format!("{}: {}", ENV_PLAYLIST, why))?;
+        defaults.playlist = Some(playlist);
+    }
+
+    Ok(Config {
+        profile: name,
+        auth,
+        defaults,
+        token_storage: file.token_storage,
+        client_id: env::var(ENV_CLIENT_ID).ok().or(file.client_id),
+        client_secret: env::var(ENV_CLIENT_SECRET).ok().or(file.client_secret),
+    })
+}
+
+/// Write `auth` into the config file section of `profile`.
+pub fn save_auth(profile: &str, auth: AuthTokens) -> Result<(), Box<dyn Error>> {
+    let mut file = load_config_file()?;
+    file.profiles.entry(profile.to_string()).or_default().auth = auth;
+
+    save_config_file(file)
+}
+
+/// Upgrade a raw config file to `CONFIG_VERSION`, returning whether anything
+/// changed.
+fn migrate(mut table: Table) -> Result<(Table, bool), Box<dyn Error>> {
+    let version = match table.get("version") {
+        Some(Value::Integer(version)) => *version as u32,
+        Some(_) => return Err("Config file version must be a number.".into()),
+        None => 1,
+    };
+
+    if version > CONFIG_VERSION {
+        return Err(format!(
+            "Config file version {} was written by a newer SpotR, this one supports up to {}.",
+            version, CONFIG_VERSION
+        )
+        .into());
+    }
+
+    for migration in &MIGRATIONS[(version.max(1) - 1) as usize..] {
+        migration(&mut table);
+    }
+    table.insert("version".to_string(), Value::Integer(CONFIG_VERSION.into()));
+
+    Ok((table, version != CONFIG_VERSION))
+}
+
+/// Version 1 kept a single account in top level `auth`/`defaults` tables,
+/// these now live in the default profile.
+fn migrate_v1_to_v2(table: &mut Table) {
+    let mut profile = Table::new();
+    for key in &["auth", "defaults"] {
+        if let Some(value) = table.remove(*key) {
+            profile.insert(key.to_string(), value);
+        }
+    }
+
+    if !profile.is_empty() {
+        let profiles = table
+            .entry("profiles".to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(profiles) = profiles {
+            profiles.insert(DEFAULT_PROFILE.to_string(), Value::Table(profile));
+        }
+    }
+}
+
+/// Check a profile name, which ends up in file names so may only use ASCII
+/// letters, digits, `-` and `_`.
+pub fn validate_profile(value: &str) -> Result<String, Box<dyn Error>> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(value.to_string())
+    } else {
+        Err(format!("{} is not a valid profile name, use only letters, digits, - and _", value).into())
+    }
+}
+
+/// Check a device ID as listed by the Spotify devices endpoint.
+pub fn validate_device(value: &str) -> Result<String, Box<dyn Error>> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric()) {
+        Ok(value.to_string())
+    } else {
+        Err(format!("{} is not a Spotify device ID", value).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_v1() {
+        let table: Table = toml::from_str(
+            r#"
+            [auth]
+            refresh_token = "refresh"
+
+            [defaults]
+            device = "abc123"
+        "#,
+        )
+        .unwrap();
+
+        let (table, migrated) = migrate(table).unwrap();
+        assert!(migrated);
+
+        let file: ConfigFile = Value::Table(table).try_into().unwrap();
+        let profile = &file.profiles[DEFAULT_PROFILE];
+        assert_eq!(file.version, CONFIG_VERSION);
+        assert_eq!(profile.auth.refresh_token.as_deref(), Some("refresh"));
+        assert_eq!(profile.defaults.device.as_deref(), Some("abc123"));
+    }
+
+    #[test]
+    fn test_validate_profile() {
+        assert!(validate_profile("work_2-b").is_ok());
+        for name in &["", "../x", "a/b", "a.b", "é"] {
+            assert!(validate_profile(name).is_err());
+        }
+    }
+
+    #[test]
+    fn test_invalid_default_playlist() {
+        let file: ConfigFile = toml::from_str("[profiles.work.defaults]\nplaylist = \"mixtape\"").unwrap();
+        let config = Config {
+            profile: "work".to_string(),
+            defaults: file.profiles["work"].defaults.clone(),
+            ..Default::default()
+        };
+
+        let why = config.default_playlist().unwrap_err().to_string();
+        assert!(why.starts_with("playlist of profile work in the config file: "), "{}", why);
+    }
+
+    #[test]
+    fn test_migrate_newer_version() {
+        let table: Table = toml::from_str("version = 999").unwrap();
+        assert!(migrate(table).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//! Local log of everything played
+//!
+//! Spotify only remembers the last 50 plays, so every play seen is appended
+//! to `<data dir>/history/<profile>.jsonl`, one JSON object per line, skipping
+//! plays already logged with the same `played_at`. Genres and audio features
+//! of what was played are kept in `<data dir>/history/metadata.json`, so
+//! statistics can be computed offline.
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_dir;
+use crate::smart::Features;
+
+/// One play of a track.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Play {
+    pub played_at: DateTime<Utc>,
+    pub uri: String,
+    pub name: String,
+    pub artists: String,
+    /// Missing from plays logged before artist IDs were.
+    #[serde(default)]
+    pub artist_ids: Vec<String>,
+    pub album: String,
+    pub duration_ms: u64,
+    /// Playlist, album or artist it was played from.
+    pub context: Option<String>,
+}
+
+/// Catalog details of what was played, shared by all profiles.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    /// By artist ID.
+    pub artists: HashMap<String, Artist>,
+    /// By track ID.
+    pub features: HashMap<String, Features>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Artist {
+    pub name: String,
+    pub genres: Vec<String>,
+}
+
+impl Play {
+    /// Track ID, from the URI.
+    pub fn track_id(&self) -> Option<&str> {
+        self.uri.strip_prefix("spotify:track:")
+    }
+}
+
+/// Path of the log of `profile`.
+pub fn log_path(profile: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = data_dir().ok_or("Cannot find the data directory.")?;
+    Ok(dir.join("history").join(format!("{}.jsonl", profile)))
+}
+
+/// Every logged play of `profile`, oldest first.
+pub fn load(profile: &str) -> Result<Vec<Play>, Box<dyn Error>> {
+    let path = log_path(profile)?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(why) if why.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(why) => return Err(format!("Cannot read {}: {}", path.display(), why).into()),
+    };
+
+    let mut plays = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let play = serde_json::from_str(line)
+            .map_err(|why| format!("{} line {}: {}", path.display(), number + 1, why))?;
+        plays.push(play);
+    }
+    plays.sort_by_key(|play: &Play| play.played_at);
+    Ok(plays)
+}
+
+/// Plays in `plays` not in `logged`, oldest first and each `played_at` once.
+pub fn unlogged(logged: &[Play], plays: &[Play]) -> Vec<Play> {
+    let mut seen: HashSet<DateTime<Utc>> = logged.iter().map(|play| play.played_at).collect();
+    let mut new: Vec<Play> = plays
+        .iter()
+        .filter(|play| seen.insert(play.played_at))
+        .cloned()
+        .collect();
+    new.sort_by_key(|play| play.played_at);
+    new
+}
+
+/// Append the plays not logged yet to the log of `profile`, returning how
+/// many were.
+pub fn append(profile: &str, plays: &[Play]) -> Result<usize, Box<dyn Error>> {
+    let new = unlogged(&load(profile)?, plays);
+    if new.is_empty() {
+        return Ok(0);
+    }
+
+    let path = log_path(profile)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    let mut lines = String::new();
+    for play in &new {
+        lines.push_str(&serde_json::to_string(play)?);
+        lines.push('\n');
+    }
+    file.write_all(lines.as_bytes())?;
+
+    Ok(new.len())
+}
+
+fn metadata_path() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = data_dir().ok_or("Cannot find the data directory.")?;
+    Ok(dir.join("history").join("metadata.json"))
+}
+
+/// Stored genres and audio features, empty if none were stored yet.
+pub fn load_metadata() -> Result<Metadata, Box<dyn Error>> {
+    let path = metadata_path()?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(why) if why.kind() == ErrorKind::NotFound => Ok(Metadata::default()),
+        Err(why) => Err(format!("Cannot read {}: {}", path.display(), why).into()),
+    }
+}
+
+pub fn save_metadata(metadata: &Metadata) -> Result<(), Box<dyn Error>> {
+    let path = metadata_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(metadata)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn play(minute: u32, uri: &str) -> Play {
+        Play {
+            played_at: Utc.ymd(2021, 3, 1).and_hms(12, minute, 0),
+            uri: uri.to_string(),
+            name: String::new(),
+            artists: String::new(),
+            artist_ids: Vec::new(),
+            album: String::new(),
+            duration_ms: 0,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_unlogged() {
+        let logged = vec![play(0, "a"), play(4, "b")];
+        let fetched = vec![play(12, "a"), play(8, "c"), play(4, "b"), play(8, "c")];
+        assert_eq!(unlogged(&logged, &fetched), vec![play(8, "c"), play(12, "a")]);
+    }
+}
@@ -1,5 +1,14 @@
 pub mod args;
 pub mod config;
 pub mod auth;
+pub mod store;
+pub mod profile;
+pub mod settings;
+pub mod output;
+pub mod snapshot;
+pub mod smart;
+pub mod history;
+pub mod top;
+pub mod stats;
 pub mod api;
 pub mod model;
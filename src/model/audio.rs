@@ -31,12 +31,12 @@ pub struct AudioFeatures {
     pub valence: f32,
 }
 
-/// Audio feature object wrapped by `Vec`
+/// Audio feature object wrapped by `Vec`, `None` for tracks without features
 ///
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-several-audio-features)
 #[derive(Deserialize)]
 pub(in crate) struct AudioFeaturesPayload {
-    pub audio_features: Vec<AudioFeatures>,
+    pub audio_features: Vec<Option<AudioFeatures>>,
 }
 
 /// Audio analysis object
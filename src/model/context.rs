@@ -20,6 +20,15 @@ pub struct Context {
     pub _type: Type,
 }
 
+/// The user's queue
+///
+/// [Reference](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-queue)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerQueue {
+    pub currently_playing: Option<PlayingItem>,
+    pub queue: Vec<PlayingItem>,
+}
+
 /// Currently playing object
 ///
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-recently-played)
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use strum::ToString;
+use strum::{EnumString, ToString};
 
 use super::Country;
 
@@ -27,12 +27,15 @@ pub enum DisallowKey {
 /// Time range: `long-term`, `medium-term`, `short-term`.
 ///
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/personalization/get-users-top-artists-and-tracks/)
-#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString)]
+#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString, EnumString)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum TimeRange {
+    #[strum(to_string = "long_term", serialize = "long")]
     LongTerm,
+    #[strum(to_string = "medium_term", serialize = "medium")]
     MediumTerm,
+    #[strum(to_string = "short_term", serialize = "short")]
     ShortTerm,
 }
 
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use strum::ToString;
+use strum::{EnumString, ToString};
 
 /// Copyright type: `C` = the copyright, `P` = the sound recording (performance)
 /// copyright.
@@ -18,7 +18,7 @@ pub enum CopyrightType {
 /// Album type: `album`, `single`, `appears_on`, `compilation`
 ///
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/object-model/#album-object-full)
-#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString)]
+#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString, EnumString)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum AlbumType {
@@ -29,7 +29,7 @@ pub enum AlbumType {
 }
 
 /// Type: `artist`, `album`, `track`, `playlist`, `show` or `episode`
-#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString)]
+#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString, EnumString)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum Type {
@@ -71,7 +71,7 @@ pub enum CurrentlyPlayingType {
 /// Type for search: `artist`, `album`, `track`, `playlist`, `show`, `episode`
 ///
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/#category-search)
-#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString)]
+#[derive(Clone, Serialize, Deserialize, Copy, PartialEq, Eq, Debug, ToString, EnumString)]
 #[serde(rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum SearchType {
@@ -124,7 +124,11 @@ pub enum DeviceType {
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum RecommendationsSeedType {
+    // The API sends these in upper case.
+    #[serde(alias = "ARTIST")]
     Artist,
+    #[serde(alias = "TRACK")]
     Track,
+    #[serde(alias = "GENRE")]
     Genre,
 }
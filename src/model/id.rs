@@ -0,0 +1,318 @@
+//! Spotify URIs, IDs and URLs
+//!
+//! Every item type gets its own ID type that can be parsed from a
+//! `spotify:track:...` URI, an `https://open.spotify.com/...` URL or, for
+//! everything but `ItemId`, a bare ID.
+use serde::{Deserialize, Serialize};
+use std::{convert::TryFrom, error::Error, fmt, str::FromStr};
+
+use crate::model::Type;
+
+const URL_PREFIX: &str = "https://open.spotify.com/";
+
+/// Error returned when parsing a Spotify URI, URL or ID fails.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdError {
+    /// Not a URI or URL SpotR understands.
+    Unrecognized(String),
+    /// The ID isn't valid base62, or is empty for user IDs.
+    InvalidId(String),
+    /// A valid URI or URL for a different type of item.
+    WrongType { expected: Type, found: String },
+}
+
+impl fmt::Display for IdError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdError::Unrecognized(value) => {
+                write!(f, "{} is not a Spotify URI, URL or ID", value)
+            }
+            IdError::InvalidId(id) => write!(f, "{} is not a valid Spotify ID", id),
+            IdError::WrongType { expected, found } => write!(
+                f,
+                "expected a {} but got a {}",
+                expected.to_string(),
+                found
+            ),
+        }
+    }
+}
+
+impl Error for IdError {}
+
+/// Split a URI or URL into its type and ID, ignoring query strings and
+/// `intl-xx` locale segments. Returns `None` for anything else.
+fn split(value: &str) -> Option<(&str, &str)> {
+    let value = value.trim();
+
+    if let Some(rest) = value.strip_prefix("spotify:") {
+        let parts: Vec<&str> = rest.split(':').collect();
+        return match parts.as_slice() {
+            [kind, id] => Some((kind, id)),
+            // Legacy `spotify:user:<user>:playlist:<id>` URIs.
+            ["user", _, "playlist", id] => Some(("playlist", id)),
+            _ => None,
+        };
+    }
+
+    let rest = value
+        .strip_prefix(URL_PREFIX)
+        .or_else(|| value.strip_prefix("http://open.spotify.com/"))
+        .or_else(|| value.strip_prefix("open.spotify.com/"))?;
+    let path = rest.split(['?', '#']).next()?;
+    let mut segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty() && !segment.starts_with("intl-"));
+
+    match (segments.next(), segments.next(), segments.next(), segments.next()) {
+        (Some(kind), Some(id), None, None) => Some((kind, id)),
+        (Some("user"), Some(_), Some("playlist"), Some(id)) => Some(("playlist", id)),
+        _ => None,
+    }
+}
+
+fn is_base62(id: &str) -> bool {
+    id.len() == 22 && id.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+fn parse_type(kind: &str) -> Option<Type> {
+    Type::from_str(kind).ok()
+}
+
+/// Parse `value` as an ID of type `expected`.
+fn parse(value: &str, expected: Type) -> Result<String, IdError> {
+    let id = match split(value) {
+        Some((kind, id)) if parse_type(kind) == Some(expected) => id,
+        Some((kind, _)) => {
+            return Err(IdError::WrongType {
+                expected,
+                found: kind.to_string(),
+            })
+        }
+        None if !value.contains(':') && !value.contains('/') => value.trim(),
+        None => return Err(IdError::Unrecognized(value.to_string())),
+    };
+
+    let valid = match expected {
+        // User IDs are usernames for older accounts.
+        Type::User => !id.is_empty(),
+        _ => is_base62(id),
+    };
+
+    if valid {
+        Ok(id.to_string())
+    } else {
+        Err(IdError::InvalidId(id.to_string()))
+    }
+}
+
+/// Common behaviour of all ID types.
+pub trait Id {
+    const TYPE: Type;
+
+    /// The bare ID.
+    fn id(&self) -> &str;
+
+    /// `spotify:<type>:<id>`
+    fn uri(&self) -> String {
+        format!("spotify:{}:{}", Self::TYPE.to_string(), self.id())
+    }
+
+    /// `https://open.spotify.com/<type>/<id>`
+    fn url(&self) -> String {
+        format!("{}{}/{}", URL_PREFIX, Self::TYPE.to_string(), self.id())
+    }
+}
+
+macro_rules! define_id {
+    ($(#[$meta:meta])* $name:ident, $kind:expr) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(try_from = "String", into = "String")]
+        pub struct $name(String);
+
+        impl Id for $name {
+            const TYPE: Type = $kind;
+
+            fn id(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = IdError;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                parse(value, $kind).map($name)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = IdError;
+
+            fn try_from(value: String) -> Result<Self, Self::Error> {
+                value.parse()
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(id: $name) -> Self {
+                id.uri()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}", self.uri())
+            }
+        }
+    };
+}
+
+define_id!(
+    /// Track ID, e.g. `spotify:track:6rqhFgbbKwnb9MLmUQDhG6`
+    TrackId,
+    Type::Track
+);
+define_id!(
+    /// Album ID, e.g. `spotify:album:6akEvsycLGftJxYudPjmqK`
+    AlbumId,
+    Type::Album
+);
+define_id!(
+    /// Artist ID, e.g. `spotify:artist:0OdUWJ0sBjDrqHygGUXeCF`
+    ArtistId,
+    Type::Artist
+);
+define_id!(
+    /// Playlist ID, e.g. `spotify:playlist:37i9dQZF1DXcBWIGoYBM5M`
+    PlaylistId,
+    Type::Playlist
+);
+define_id!(
+    /// Show ID, e.g. `spotify:show:5CfCWKI5pZ28U0uOzXkDHe`
+    ShowId,
+    Type::Show
+);
+define_id!(
+    /// Episode ID, e.g. `spotify:episode:512ojhOuo1ktJprKbVcKyQ`
+    EpisodeId,
+    Type::Episode
+);
+define_id!(
+    /// User ID, e.g. `spotify:user:wizzler`
+    UserId,
+    Type::User
+);
+
+/// Any playable item or context. Bare IDs are rejected since their type
+/// can't be known.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ItemId {
+    Track(TrackId),
+    Album(AlbumId),
+    Artist(ArtistId),
+    Playlist(PlaylistId),
+    Show(ShowId),
+    Episode(EpisodeId),
+}
+
+impl ItemId {
+    pub fn uri(&self) -> String {
+        match self {
+            ItemId::Track(id) => id.uri(),
+            ItemId::Album(id) => id.uri(),
+            ItemId::Artist(id) => id.uri(),
+            ItemId::Playlist(id) => id.uri(),
+            ItemId::Show(id) => id.uri(),
+            ItemId::Episode(id) => id.uri(),
+        }
+    }
+
+    /// Whether the item is a context (album, artist, playlist or show) rather
+    /// than a single track or episode.
+    pub fn is_context(&self) -> bool {
+        !matches!(self, ItemId::Track(_) | ItemId::Episode(_))
+    }
+}
+
+impl FromStr for ItemId {
+    type Err = IdError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let kind = split(value)
+            .and_then(|(kind, _)| parse_type(kind))
+            .ok_or_else(|| IdError::Unrecognized(value.to_string()))?;
+
+        Ok(match kind {
+            Type::Track => ItemId::Track(value.parse()?),
+            Type::Album => ItemId::Album(value.parse()?),
+            Type::Artist => ItemId::Artist(value.parse()?),
+            Type::Playlist => ItemId::Playlist(value.parse()?),
+            Type::Show => ItemId::Show(value.parse()?),
+            Type::Episode => ItemId::Episode(value.parse()?),
+            Type::User => {
+                return Err(IdError::Unrecognized(value.to_string()));
+            }
+        })
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.uri())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "6rqhFgbbKwnb9MLmUQDhG6";
+
+    #[test]
+    fn test_parse_formats() {
+        let uri = format!("spotify:track:{}", ID);
+        for value in &[
+            uri.clone(),
+            ID.to_string(),
+            format!("https://open.spotify.com/track/{}", ID),
+            format!("https://open.spotify.com/track/{}?si=abcdef", ID),
+            format!("https://open.spotify.com/intl-de/track/{}", ID),
+        ] {
+            let id: TrackId = value.parse().unwrap();
+            assert_eq!(id.id(), ID);
+            assert_eq!(id.uri(), uri);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            format!("spotify:album:{}", ID).parse::<TrackId>(),
+            Err(IdError::WrongType {
+                expected: Type::Track,
+                found: "album".to_string()
+            })
+        );
+        assert!(matches!("tooshort".parse::<TrackId>(), Err(IdError::InvalidId(_))));
+        assert!(matches!(
+            "https://example.com/track/x".parse::<TrackId>(),
+            Err(IdError::Unrecognized(_))
+        ));
+    }
+
+    #[test]
+    fn test_item_id() {
+        let item: ItemId = format!("https://open.spotify.com/playlist/{}?si=1", ID).parse().unwrap();
+        assert!(item.is_context());
+        assert_eq!(item.uri(), format!("spotify:playlist:{}", ID));
+        assert!(ID.parse::<ItemId>().is_err());
+    }
+
+    #[test]
+    fn test_legacy_user_playlist() {
+        let id: PlaylistId = format!("spotify:user:wizzler:playlist:{}", ID).parse().unwrap();
+        assert_eq!(id.url(), format!("https://open.spotify.com/playlist/{}", ID));
+    }
+}
@@ -6,6 +6,7 @@ pub mod category;
 pub mod context;
 pub mod device;
 pub mod enums;
+pub mod id;
 pub mod image;
 pub mod offset;
 pub mod page;
@@ -221,7 +222,7 @@ pub enum PlayingItem {
 }
 
 pub use {
-    album::*, artist::*, audio::*, category::*, context::*, device::*, enums::*, image::*,
+    album::*, artist::*, audio::*, category::*, context::*, device::*, enums::*, id::*, image::*,
     offset::*, page::*, playing::*, playlist::*, recommend::*, search::*, show::*, track::*,
     user::*,
 };
@@ -1,6 +1,7 @@
 use super::image::Image;
 use super::page::Page;
 use crate::model::{duration_ms, CopyrightType, DatePrecision};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -51,7 +52,7 @@ pub(in crate) struct SeversalSimplifiedShows {
 /// [Reference](https://developer.spotify.com/documentation/web-api/reference/#object-savedshowobject)
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Show {
-    pub added_at: String,
+    pub added_at: DateTime<Utc>,
     pub show: SimplifiedShow,
 }
 
@@ -0,0 +1,52 @@
+//! Printing lists of items as text, JSON or CSV
+use std::{error::Error, io};
+
+use serde::Serialize;
+use strum::{Display, EnumString};
+
+/// Output format picked with `--format`: `text`, `json` or `csv`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+/// A row of output. JSON output uses the `Serialize` implementation.
+pub trait Record: Serialize {
+    /// Column names, written as the CSV header.
+    const COLUMNS: &'static [&'static str];
+
+    /// Values for each of `COLUMNS`.
+    fn values(&self) -> Vec<String>;
+
+    /// The record as a single line of colored text.
+    fn line(&self) -> String;
+}
+
+/// Print `records` to stdout in `format`.
+pub fn print<R: Record>(format: OutputFormat, records: &[R]) -> Result<(), Box<dyn Error>> {
+    match format {
+        OutputFormat::Text => {
+            if records.is_empty() {
+                println!("No results.");
+            }
+            for (i, record) in records.iter().enumerate() {
+                println!("{:>3}. {}", i + 1, record.line());
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(records)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(io::stdout());
+            writer.write_record(R::COLUMNS)?;
+            for record in records {
+                writer.write_record(record.values())?;
+            }
+            writer.flush()?;
+        }
+    }
+
+    Ok(())
+}
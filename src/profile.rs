@@ -0,0 +1,85 @@
+//! Managing named accounts/profiles
+use std::error::Error;
+
+use colored::Colorize;
+
+use crate::{
+    config::{load_config, load_config_file, save_config_file, validate_profile, TokenStorage},
+    store,
+};
+
+pub fn list() -> Result<(), Box<dyn Error>> {
+    let file = load_config_file()?;
+    let default = file.default_profile();
+
+    if file.profiles.is_empty() {
+        println!("No profiles yet, use sp login to create the {} profile.", default.bold());
+    }
+
+    for (name, profile) in &file.profiles {
+        let marker = if *name == default { "*" } else { " " };
+        // Only plaintext tokens can be checked without unlocking a store.
+        let status = if file.token_storage == TokenStorage::Plaintext && profile.auth.is_empty() {
+            "not logged in".italic()
+        } else {
+            "".normal()
+        };
+        println!("{} {} {}", marker, name.green(), status);
+    }
+
+    Ok(())
+}
+
+pub fn add(name: &str) -> Result<(), Box<dyn Error>> {
+    validate_profile(name)?;
+    let mut file = load_config_file()?;
+
+    if file.has_profile(name) {
+        return Err(format!("Profile {} already exists.", name).into());
+    }
+
+    file.profiles.insert(name.to_string(), Default::default());
+    save_config_file(file)?;
+
+    println!(
+        "Added profile {}, log into it with sp --profile {} login.",
+        name.green(),
+        name
+    );
+    Ok(())
+}
+
+pub fn remove(name: &str) -> Result<(), Box<dyn Error>> {
+    let file = load_config_file()?;
+
+    if !file.has_profile(name) {
+        return Err(format!("No profile named {}.", name).into());
+    }
+
+    // Tokens outside the config file have to be removed separately.
+    store::open(&load_config(Some(name))?)?.clear()?;
+
+    let mut file = load_config_file()?;
+    file.profiles.remove(name);
+    if file.default_profile.as_deref() == Some(name) {
+        file.default_profile = None;
+    }
+    save_config_file(file)?;
+
+    println!("Removed profile {}.", name.green());
+    Ok(())
+}
+
+pub fn set_default(name: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = load_config_file()?;
+
+    if !file.has_profile(name) {
+        return Err(format!("No profile named {}.", name).into());
+    }
+
+    file.default_profile = Some(name.to_string());
+    save_config_file(file)?;
+
+    println!("Now using profile {} by default.", name.green());
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! Viewing and changing the config file from the command line
+use std::{env, error::Error, process::Command};
+
+use colored::Colorize;
+
+use crate::config::{
+    config_path, load_config, load_config_file, save_config_file, validate_device, ConfigFile,
+    TokenStorage, ENV_CLIENT_ID, ENV_CLIENT_SECRET, ENV_DEVICE, ENV_PLAYLIST, ENV_PROFILE,
+};
+use crate::model::{Id, PlaylistId};
+
+/// Keys accepted by `sp config get|set|unset`, with the environment variable
+/// overriding each one.
+pub const KEYS: &[(&str, Option<&str>)] = &[
+    ("playlist", Some(ENV_PLAYLIST)),
+    ("device", Some(ENV_DEVICE)),
+    ("default_profile", Some(ENV_PROFILE)),
+    ("token_storage", None),
+    ("client_id", Some(ENV_CLIENT_ID)),
+    ("client_secret", Some(ENV_CLIENT_SECRET)),
+];
+
+pub fn path() -> Result<(), Box<dyn Error>> {
+    println!("{}", config_path()?.display());
+    Ok(())
+}
+
+/// Open the config file in `$VISUAL`/`$EDITOR` and check it still loads.
+pub fn edit() -> Result<(), Box<dyn Error>> {
+    let path = config_path()?;
+    if !path.exists() {
+        save_config_file(load_config_file()?)?;
+    }
+
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+
+    let status = Command::new(&editor).arg(&path).status()?;
+    if !status.success() {
+        return Err(format!("{} exited with {}", editor, status).into());
+    }
+
+    load_config_file()?;
+    println!("{}", "Config file is valid.".green());
+    Ok(())
+}
+
+pub fn get(profile: &str, key: &str) -> Result<(), Box<dyn Error>> {
+    match value(&load_config_file()?, profile, key)? {
+        Some(value) => println!("{}", value),
+        None => println!("{}", "unset".italic()),
+    }
+    Ok(())
+}
+
+pub fn set(profile: &str, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = load_config_file()?;
+
+    match key {
+        "playlist" | "device" => {
+            let defaults = &mut file.profiles.entry(profile.to_string()).or_default().defaults;
+            if key == "playlist" {
+                defaults.playlist = Some(value.parse::<PlaylistId>()?.uri());
+            } else {
+                defaults.device = Some(validate_device(value)?);
+            }
+        }
+        "default_profile" => {
+            if !file.has_profile(value) {
+                return Err(format!("No profile named {}.", value).into());
+            }
+            file.default_profile = Some(value.to_string());
+        }
+        "token_storage" => {
+            file.token_storage = match value {
+                "plaintext" => TokenStorage::Plaintext,
+                "secret_service" => TokenStorage::SecretService,
+                "encrypted_file" => TokenStorage::EncryptedFile,
+                _ => {
+                    return Err(String::from(
+                        "token_storage must be plaintext, secret_service or encrypted_file",
+                    )
+                    .into())
+                }
+            };
+            if file.token_storage != TokenStorage::Plaintext {
+                println!("Tokens will be moved into the new store the next time sp runs.");
+            }
+        }
+        "client_id" => file.client_id = Some(value.to_string()),
+        "client_secret" => file.client_secret = Some(value.to_string()),
+        _ => return Err(unknown_key(key)),
+    }
+
+    save_config_file(file)?;
+    println!("Set {}.", key.green());
+    Ok(())
+}
+
+pub fn unset(profile: &str, key: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = load_config_file()?;
+
+    match key {
+        "playlist" | "device" => {
+            if let Some(entry) = file.profiles.get_mut(profile) {
+                if key == "playlist" {
+                    entry.defaults.playlist = None;
+                } else {
+                    entry.defaults.device = None;
+                }
+            }
+        }
+        "default_profile" => file.default_profile = None,
+        "token_storage" => file.token_storage = TokenStorage::default(),
+        "client_id" => file.client_id = None,
+        "client_secret" => file.client_secret = None,
+        _ => return Err(unknown_key(key)),
+    }
+
+    save_config_file(file)?;
+    println!("Unset {}.", key.green());
+    Ok(())
+}
+
+/// Print every setting in effect for `profile`, including environment
+/// overrides. Secrets are masked.
+pub fn show(profile: &str) -> Result<(), Box<dyn Error>> {
+    // Loading the profile checks any environment overrides are valid.
+    load_config(Some(profile))?;
+    let file = load_config_file()?;
+
+    println!("{} {}", "Profile".bold(), profile.green());
+    for (key, env_var) in KEYS {
+        let overridden = env_var.filter(|var| env::var(var).is_ok());
+        let value = match overridden {
+            Some(var) => env::var(var).ok(),
+            None => value(&file, profile, key)?,
+        };
+        let value = match (value, *key) {
+            (Some(_), "client_secret") => "********".to_string(),
+            (Some(value), _) => value,
+            (None, _) => "unset".italic().to_string(),
+        };
+
+        match overridden {
+            Some(var) => println!("{:>16}: {} {}", key, value, format!("(from {})", var).dimmed()),
+            None => println!("{:>16}: {}", key, value),
+        }
+    }
+
+    Ok(())
+}
+
+fn value(file: &ConfigFile, profile: &str, key: &str) -> Result<Option<String>, Box<dyn Error>> {
+    let defaults = file.profiles.get(profile).map(|profile| &profile.defaults);
+
+    Ok(match key {
+        "playlist" => defaults.and_then(|defaults| defaults.playlist.clone()),
+        "device" => defaults.and_then(|defaults| defaults.device.clone()),
+        // As stored, SPOTR_PROFILE is only shown by `sp config show`.
+        "default_profile" => file.default_profile.clone(),
+        "token_storage" => Some(
+            match file.token_storage {
+                TokenStorage::Plaintext => "plaintext",
+                TokenStorage::SecretService => "secret_service",
+                TokenStorage::EncryptedFile => "encrypted_file",
+            }
+            .to_string(),
+        ),
+        "client_id" => file.client_id.clone(),
+        "client_secret" => file.client_secret.clone(),
+        _ => return Err(unknown_key(key)),
+    })
+}
+
+fn unknown_key(key: &str) -> Box<dyn Error> {
+    let keys: Vec<_> = KEYS.iter().map(|(key, _)| *key).collect();
+    format!("Unknown config key {}, expected one of: {}", key, keys.join(", ")).into()
+}
@@ -0,0 +1,378 @@
+//! Smart playlists: rule files selecting tracks from the library
+//!
+//! A rule is a TOML file, looked up in `<config dir>/smart/<name>.toml` unless
+//! a path is given:
+//!
+//! ```toml
+//! name = "Fresh Energy"
+//! limit = 100
+//! sort = "added_at"
+//! reverse = true
+//!
+//! [filter]
+//! added_within_days = 30
+//! energy = { min = 0.7 }
+//! exclude_artists = ["Nickelback"]
+//! ```
+//!
+//! Evaluating a rule is pure so it can be tested against fixture data.
+use std::{convert::TryFrom, error::Error, fs, path::PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{sorted_order, SortKey, SortValue};
+use crate::config::config_dir;
+use crate::model::{AudioFeatures, PlaylistId};
+
+/// Where a rule's tracks come from: `liked` or a playlist.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Source {
+    #[default]
+    Liked,
+    Playlist(PlaylistId),
+}
+
+impl TryFrom<String> for Source {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        if value == "liked" {
+            return Ok(Source::Liked);
+        }
+        value
+            .parse()
+            .map(Source::Playlist)
+            .map_err(|_| format!("{} is neither liked nor a playlist", value))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Name of the playlist created on the first sync.
+    pub name: String,
+    pub description: Option<String>,
+    /// Playlist the tracks are written to, filled in on the first sync.
+    pub playlist: Option<PlaylistId>,
+    #[serde(default)]
+    pub source: Source,
+    #[serde(default)]
+    pub filter: Filter,
+    pub sort: Option<SortKey>,
+    #[serde(default)]
+    pub reverse: bool,
+    pub limit: Option<usize>,
+}
+
+/// Conditions a track must all meet. Artist and genre names are compared
+/// case-insensitively; a genre matches every genre containing it.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Filter {
+    pub added_within_days: Option<i64>,
+    /// Release dates are compared as written, e.g. `2020` or `2020-06-01`.
+    pub released_after: Option<String>,
+    pub released_before: Option<String>,
+    pub popularity: Range,
+    pub energy: Range,
+    pub tempo: Range,
+    pub danceability: Range,
+    pub valence: Range,
+    pub artists: Vec<String>,
+    pub exclude_artists: Vec<String>,
+    pub genres: Vec<String>,
+    pub exclude_genres: Vec<String>,
+}
+
+/// Inclusive bounds, either of which may be left out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Range {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+impl Range {
+    fn is_set(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// Whether `value` is within bounds. Without a value only an unset range
+    /// matches.
+    fn contains(&self, value: Option<f64>) -> bool {
+        match value {
+            Some(value) => self.min.map_or(true, |min| value >= min) && self.max.map_or(true, |max| value <= max),
+            None => !self.is_set(),
+        }
+    }
+}
+
+/// The audio features rules and listening statistics use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    pub energy: f32,
+    pub tempo: f32,
+    pub danceability: f32,
+    pub valence: f32,
+    pub key: i32,
+}
+
+impl From<&AudioFeatures> for Features {
+    fn from(features: &AudioFeatures) -> Self {
+        Features {
+            energy: features.energy,
+            tempo: features.tempo,
+            danceability: features.danceability,
+            valence: features.valence,
+            key: features.key,
+        }
+    }
+}
+
+/// A track a rule may select.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Candidate {
+    pub uri: String,
+    pub name: String,
+    pub artists: Vec<String>,
+    pub album: String,
+    pub added_at: Option<DateTime<Utc>>,
+    pub release_date: Option<String>,
+    pub popularity: u32,
+    /// Only fetched when the rule needs them.
+    pub features: Option<Features>,
+    /// Genres of all the track's artists, only fetched when the rule needs
+    /// them.
+    pub genres: Vec<String>,
+}
+
+impl Candidate {
+    fn sort_value(&self, key: SortKey) -> Option<SortValue> {
+        let feature = |get: fn(&Features) -> f32| self.features.as_ref().map(|f| SortValue::Number(get(f).into()));
+        match key {
+            SortKey::AddedAt => self.added_at.map(|at| SortValue::Text(at.to_rfc3339())),
+            SortKey::Artist => self.artists.first().map(|artist| SortValue::Text(artist.to_lowercase())),
+            SortKey::Album => Some(SortValue::Text(self.album.to_lowercase())),
+            SortKey::ReleaseDate => self.release_date.clone().map(SortValue::Text),
+            SortKey::Popularity => Some(SortValue::Number(self.popularity.into())),
+            SortKey::Tempo => feature(|f| f.tempo),
+            SortKey::Energy => feature(|f| f.energy),
+            SortKey::Key => self
+                .features
+                .filter(|f| f.key >= 0)
+                .map(|f| SortValue::Number(f.key.into())),
+        }
+    }
+}
+
+impl Rule {
+    /// Whether evaluating the rule needs audio features.
+    pub fn needs_features(&self) -> bool {
+        let filter = &self.filter;
+        [filter.energy, filter.tempo, filter.danceability, filter.valence]
+            .iter()
+            .any(Range::is_set)
+            || matches!(self.sort, Some(SortKey::Tempo) | Some(SortKey::Energy) | Some(SortKey::Key))
+    }
+
+    /// Whether evaluating the rule needs artist genres.
+    pub fn needs_genres(&self) -> bool {
+        !self.filter.genres.is_empty() || !self.filter.exclude_genres.is_empty()
+    }
+
+    fn matches(&self, candidate: &Candidate, now: DateTime<Utc>) -> bool {
+        let filter = &self.filter;
+        let feature = |get: fn(&Features) -> f32| candidate.features.as_ref().map(|f| get(f).into());
+        let any_artist = |names: &[String]| {
+            candidate
+                .artists
+                .iter()
+                .any(|artist| names.iter().any(|name| name.eq_ignore_ascii_case(artist)))
+        };
+        let any_genre = |genres: &[String]| {
+            candidate
+                .genres
+                .iter()
+                .any(|genre| genres.iter().any(|wanted| genre.to_lowercase().contains(&wanted.to_lowercase())))
+        };
+        let released = candidate.release_date.as_deref();
+
+        let added = filter
+            .added_within_days
+            .map_or(true, |days| candidate.added_at.map_or(false, |at| at >= now - Duration::days(days)));
+        let after = filter
+            .released_after
+            .as_deref()
+            .map_or(true, |after| released.map_or(false, |date| date >= after));
+        let before = filter
+            .released_before
+            .as_deref()
+            .map_or(true, |before| released.map_or(false, |date| date < before));
+
+        added
+            && after
+            && before
+            && filter.popularity.contains(Some(candidate.popularity.into()))
+            && filter.energy.contains(feature(|f| f.energy))
+            && filter.tempo.contains(feature(|f| f.tempo))
+            && filter.danceability.contains(feature(|f| f.danceability))
+            && filter.valence.contains(feature(|f| f.valence))
+            && (filter.artists.is_empty() || any_artist(&filter.artists))
+            && !any_artist(&filter.exclude_artists)
+            && (filter.genres.is_empty() || any_genre(&filter.genres))
+            && !any_genre(&filter.exclude_genres)
+    }
+
+    /// The candidates the rule selects, in playlist order.
+    pub fn evaluate<'a>(&self, candidates: &'a [Candidate], now: DateTime<Utc>) -> Vec<&'a Candidate> {
+        let mut selected: Vec<&Candidate> = candidates
+            .iter()
+            .filter(|candidate| self.matches(candidate, now))
+            .collect();
+
+        if let Some(key) = self.sort {
+            let values: Vec<Option<SortValue>> = selected.iter().map(|candidate| candidate.sort_value(key)).collect();
+            selected = sorted_order(&values, self.reverse)
+                .into_iter()
+                .map(|i| selected[i])
+                .collect();
+        }
+        if let Some(limit) = self.limit {
+            selected.truncate(limit);
+        }
+
+        selected
+    }
+}
+
+fn rules_dir() -> Result<PathBuf, Box<dyn Error>> {
+    let dir = config_dir().ok_or("Cannot find the config directory.")?;
+    Ok(dir.join("smart"))
+}
+
+/// Path of the rule file `rule`, a path or the name of a file in the rules
+/// directory.
+pub fn rule_path(rule: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let path = PathBuf::from(rule);
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let path = rules_dir()?.join(format!("{}.toml", rule));
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(format!("No rule {}, rules are kept in {}.", rule, rules_dir()?.display()).into())
+    }
+}
+
+pub fn parse(contents: &str) -> Result<Rule, Box<dyn Error>> {
+    Ok(toml::from_str(contents)?)
+}
+
+pub fn load(path: &PathBuf) -> Result<Rule, Box<dyn Error>> {
+    parse(&fs::read_to_string(path)?).map_err(|why| format!("Invalid rule {}: {}", path.display(), why).into())
+}
+
+/// Names of the rules in the rules directory.
+pub fn list() -> Result<Vec<String>, Box<dyn Error>> {
+    let dir = rules_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            if let Some(name) = path.file_stem() {
+                names.push(name.to_string_lossy().into_owned());
+            }
+        }
+    }
+
+    names.sort();
+    Ok(names)
+}
+
+/// Record the playlist a rule syncs to at the top of its file, leaving the
+/// rest of it as written.
+pub fn set_playlist(path: &PathBuf, playlist: &PlaylistId) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(path)?;
+    fs::write(path, format!("playlist = \"{}\"\n{}", playlist, contents))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const RULE: &str = r#"
+        name = "Fresh Energy"
+        limit = 2
+        sort = "energy"
+        reverse = true
+
+        [filter]
+        added_within_days = 30
+        energy = { min = 0.7 }
+        exclude_artists = ["nickelback"]
+    "#;
+
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2021, 3, 1).and_hms(0, 0, 0)
+    }
+
+    fn candidate(name: &str, artist: &str, days_ago: i64, energy: f32) -> Candidate {
+        Candidate {
+            uri: format!("spotify:track:{}", name),
+            name: name.to_string(),
+            artists: vec![artist.to_string()],
+            added_at: Some(now() - Duration::days(days_ago)),
+            features: Some(Features { energy, ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    fn names(selected: &[&Candidate]) -> Vec<String> {
+        selected.iter().map(|candidate| candidate.name.clone()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let rule = parse(RULE).unwrap();
+        assert_eq!(rule.source, Source::Liked);
+        assert_eq!(rule.filter.energy, Range { min: Some(0.7), max: None });
+        assert!(rule.needs_features());
+        assert!(!rule.needs_genres());
+        assert!(parse("name = \"x\"\nlimt = 3").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let candidates = vec![
+            candidate("a", "Queen", 3, 0.8),
+            candidate("b", "Queen", 40, 0.9),
+            candidate("c", "Nickelback", 1, 0.9),
+            candidate("d", "Muse", 10, 0.95),
+            candidate("e", "Muse", 10, 0.5),
+            candidate("f", "Muse", 12, 0.75),
+        ];
+        let rule = parse(RULE).unwrap();
+        assert_eq!(names(&rule.evaluate(&candidates, now())), vec!["d", "a"]);
+    }
+
+    #[test]
+    fn test_genres() {
+        let mut indie = candidate("a", "Alvvays", 0, 0.5);
+        indie.genres = vec!["Canadian Indie".to_string()];
+        let candidates = vec![indie, candidate("b", "Queen", 0, 0.5)];
+
+        let rule = parse("name = \"Indie\"\n[filter]\ngenres = [\"indie\"]").unwrap();
+        assert!(rule.needs_genres());
+        assert_eq!(names(&rule.evaluate(&candidates, now())), vec!["a"]);
+    }
+}
@@ -0,0 +1,218 @@
+//! Local copies of playlists, keyed by snapshot ID
+//!
+//! Snapshots live in `<data dir>/snapshots/<playlist id>/`, one JSON file per
+//! snapshot, so the history of a playlist can be diffed and restored even
+//! though Spotify only serves its current state.
+use std::{collections::HashMap, error::Error, fs, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_dir;
+
+/// A playlist's items at one snapshot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub playlist_id: String,
+    pub name: String,
+    pub snapshot_id: String,
+    pub taken_at: DateTime<Utc>,
+    pub items: Vec<SnapshotItem>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotItem {
+    pub uri: String,
+    pub name: String,
+    pub artists: String,
+    pub added_at: Option<DateTime<Utc>>,
+    pub added_by: Option<String>,
+}
+
+/// A difference between two snapshots. Positions are 0-based.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change<'a> {
+    Added { item: &'a SnapshotItem, position: usize },
+    Removed { item: &'a SnapshotItem, position: usize },
+    Moved { item: &'a SnapshotItem, from: usize, to: usize },
+}
+
+fn playlist_dir(playlist_id: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = data_dir().ok_or("Cannot find the data directory.")?;
+    Ok(dir.join("snapshots").join(playlist_id))
+}
+
+/// Snapshot IDs may contain `/`, which can't be part of a file name.
+fn file_name(snapshot_id: &str) -> String {
+    format!("{}.json", snapshot_id.replace('/', "_").replace('+', "-"))
+}
+
+/// Store `snapshot`, returning `false` if it was already stored.
+pub fn save(snapshot: &Snapshot) -> Result<bool, Box<dyn Error>> {
+    let dir = playlist_dir(&snapshot.playlist_id)?;
+    let path = dir.join(file_name(&snapshot.snapshot_id));
+    if path.exists() {
+        return Ok(false);
+    }
+
+    fs::create_dir_all(&dir)?;
+    fs::write(path, serde_json::to_string(snapshot)?)?;
+    Ok(true)
+}
+
+/// All stored snapshots of a playlist, oldest first.
+pub fn list(playlist_id: &str) -> Result<Vec<Snapshot>, Box<dyn Error>> {
+    let dir = playlist_dir(playlist_id)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "json") {
+            let contents = fs::read_to_string(&path)?;
+            snapshots.push(
+                serde_json::from_str::<Snapshot>(&contents)
+                    .map_err(|why| format!("Corrupt snapshot {}: {}", path.display(), why))?,
+            );
+        }
+    }
+
+    snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+    Ok(snapshots)
+}
+
+/// Find a stored snapshot by its ID or a unique prefix of it.
+pub fn find(playlist_id: &str, snapshot_id: &str) -> Result<Snapshot, Box<dyn Error>> {
+    let mut matches: Vec<Snapshot> = list(playlist_id)?
+        .into_iter()
+        .filter(|snapshot| snapshot.snapshot_id.starts_with(snapshot_id))
+        .collect();
+
+    match matches.len() {
+        0 => Err(format!("No stored snapshot {}, see sp playlist history.", snapshot_id).into()),
+        1 => Ok(matches.remove(0)),
+        _ => Err(format!("{} matches more than one snapshot, use more characters.", snapshot_id).into()),
+    }
+}
+
+/// Changes turning `from` into `to`. The n-th occurrence of a URI in `from`
+/// is paired with its n-th occurrence in `to`, and as few pairs as possible
+/// are reported as moved.
+pub fn diff<'a>(from: &'a [SnapshotItem], to: &'a [SnapshotItem]) -> Vec<Change<'a>> {
+    let mut positions: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (position, item) in from.iter().enumerate().rev() {
+        positions.entry(&item.uri).or_default().push(position);
+    }
+
+    let mut changes = Vec::new();
+    let mut matched = vec![false; from.len()];
+    // (position in `from`, position in `to`) of items in both, in `to` order.
+    let mut pairs = Vec::new();
+
+    for (position, item) in to.iter().enumerate() {
+        match positions.get_mut(item.uri.as_str()).and_then(Vec::pop) {
+            Some(old) => {
+                matched[old] = true;
+                pairs.push((old, position));
+            }
+            None => changes.push(Change::Added { item, position }),
+        }
+    }
+
+    for (position, item) in from.iter().enumerate() {
+        if !matched[position] {
+            changes.push(Change::Removed { item, position });
+        }
+    }
+
+    let old_positions: Vec<usize> = pairs.iter().map(|(old, _)| *old).collect();
+    let stay = longest_increasing_subsequence(&old_positions);
+    let mut stay = stay.iter().peekable();
+    for (i, (old, new)) in pairs.into_iter().enumerate() {
+        if stay.peek() == Some(&&i) {
+            stay.next();
+        } else {
+            changes.push(Change::Moved { item: &to[new], from: old, to: new });
+        }
+    }
+
+    changes
+}
+
+/// Indices of a longest strictly increasing subsequence of `values`.
+pub fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+    // `tails[k]` is the index of the smallest tail of an increasing
+    // subsequence of length `k + 1`.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut previous = vec![None; values.len()];
+
+    for (i, value) in values.iter().enumerate() {
+        let k = tails.partition_point(|&tail| values[tail] < *value);
+        if k > 0 {
+            previous[i] = Some(tails[k - 1]);
+        }
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+
+    let mut sequence = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        sequence.push(i);
+        current = previous[i];
+    }
+    sequence.reverse();
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(uris: &[&str]) -> Vec<SnapshotItem> {
+        uris.iter()
+            .map(|uri| SnapshotItem {
+                uri: uri.to_string(),
+                name: uri.to_string(),
+                artists: String::new(),
+                added_at: None,
+                added_by: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lis() {
+        assert_eq!(longest_increasing_subsequence(&[3, 0, 1, 4, 2, 5]), vec![1, 2, 4, 5]);
+        assert!(longest_increasing_subsequence(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = items(&["a", "b", "c", "d"]);
+        let to = items(&["b", "c", "a", "e"]);
+        let changes = diff(&from, &to);
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Added { item: &to[3], position: 3 },
+                Change::Removed { item: &from[3], position: 3 },
+                Change::Moved { item: &to[2], from: 0, to: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_duplicates() {
+        let from = items(&["a", "a", "b"]);
+        let to = items(&["a", "b"]);
+        assert_eq!(diff(&from, &to), vec![Change::Removed { item: &from[1], position: 1 }]);
+        assert!(diff(&from, &from).is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! Listening statistics computed from the local history log
+use std::collections::{HashMap, HashSet};
+
+use chrono::{Datelike, TimeZone, Timelike};
+use serde::Serialize;
+
+use crate::history::{Metadata, Play};
+
+/// Plays and time played of one track, artist, album or genre.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Tally {
+    pub name: String,
+    /// Artists of a track or album.
+    pub detail: String,
+    pub plays: usize,
+    pub duration_ms: u64,
+}
+
+/// Audio features averaged over the plays they are known for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct Averages {
+    pub plays: usize,
+    pub energy: f32,
+    pub danceability: f32,
+    pub valence: f32,
+    pub tempo: f32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub plays: usize,
+    /// Length of the tracks played, assuming they were played to the end.
+    pub duration_ms: u64,
+    pub tracks: Vec<Tally>,
+    pub artists: Vec<Tally>,
+    pub albums: Vec<Tally>,
+    /// Plays of tracks by artists of each genre.
+    pub genres: Vec<Tally>,
+    /// Plays by day of the week, Monday first, and hour of the day.
+    pub heatmap: [[usize; 24]; 7],
+    pub features: Option<Averages>,
+}
+
+/// Counts plays and time played by key.
+#[derive(Default)]
+struct Counter(HashMap<String, Tally>);
+
+impl Counter {
+    fn add(&mut self, key: &str, name: &str, detail: &str, duration_ms: u64) {
+        let tally = self.0.entry(key.to_string()).or_insert_with(|| Tally {
+            name: name.to_string(),
+            detail: detail.to_string(),
+            ..Default::default()
+        });
+        tally.plays += 1;
+        tally.duration_ms += duration_ms;
+    }
+
+    /// The `top` most played, ties broken by time played and then by name.
+    fn top(self, top: usize) -> Vec<Tally> {
+        let mut tallies: Vec<Tally> = self.0.into_values().collect();
+        tallies.sort_by(|a, b| {
+            b.plays
+                .cmp(&a.plays)
+                .then(b.duration_ms.cmp(&a.duration_ms))
+                .then(a.name.cmp(&b.name))
+        });
+        tallies.truncate(top);
+        tallies
+    }
+}
+
+/// `duration_ms` in whole minutes, rounded to the nearest one.
+pub fn minutes(duration_ms: u64) -> u64 {
+    (duration_ms + 30_000) / 60_000
+}
+
+/// Statistics of `plays`, with the `top` tracks, artists, albums and genres,
+/// and the heatmap in the time zone `tz`.
+pub fn compute<Tz: TimeZone>(plays: &[Play], metadata: &Metadata, tz: &Tz, top: usize) -> Stats {
+    let mut stats = Stats::default();
+    let (mut tracks, mut artists, mut albums, mut genres) =
+        (Counter::default(), Counter::default(), Counter::default(), Counter::default());
+    let mut features = Averages::default();
+
+    for play in plays {
+        let duration = play.duration_ms;
+        stats.plays += 1;
+        stats.duration_ms += duration;
+
+        tracks.add(&play.uri, &play.name, &play.artists, duration);
+        albums.add(&format!("{}\n{}", play.album, play.artists), &play.album, &play.artists, duration);
+
+        // Plays logged before artist IDs were count for all artists at once.
+        if play.artist_ids.is_empty() {
+            artists.add(&play.artists, &play.artists, "", duration);
+        }
+        let mut play_genres = HashSet::new();
+        for id in &play.artist_ids {
+            let artist = metadata.artists.get(id);
+            artists.add(id, artist.map_or(id.as_str(), |artist| &artist.name), "", duration);
+            play_genres.extend(artist.into_iter().flat_map(|artist| artist.genres.iter()));
+        }
+        for genre in play_genres {
+            genres.add(genre, genre, "", duration);
+        }
+
+        let played_at = play.played_at.with_timezone(tz);
+        stats.heatmap[played_at.weekday().num_days_from_monday() as usize][played_at.hour() as usize] += 1;
+
+        if let Some(known) = play.track_id().and_then(|id| metadata.features.get(id)) {
+            features.plays += 1;
+            features.energy += known.energy;
+            features.danceability += known.danceability;
+            features.valence += known.valence;
+            features.tempo += known.tempo;
+        }
+    }
+
+    stats.tracks = tracks.top(top);
+    stats.artists = artists.top(top);
+    stats.albums = albums.top(top);
+    stats.genres = genres.top(top);
+    if features.plays > 0 {
+        let count = features.plays as f32;
+        stats.features = Some(Averages {
+            plays: features.plays,
+            energy: features.energy / count,
+            danceability: features.danceability / count,
+            valence: features.valence / count,
+            tempo: features.tempo / count,
+        });
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Artist;
+    use crate::smart::Features;
+    use chrono::Utc;
+
+    fn play(day: u32, hour: u32, track: &str, artist: &str, duration_ms: u64) -> Play {
+        Play {
+            played_at: Utc.ymd(2021, 3, day).and_hms(hour, 0, 0),
+            uri: format!("spotify:track:{}", track),
+            name: track.to_string(),
+            artists: artist.to_string(),
+            artist_ids: vec![artist.to_string()],
+            album: format!("{} album", artist),
+            duration_ms,
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_compute() {
+        let mut metadata = Metadata::default();
+        for (id, genres) in &[("a", vec!["indie", "folk"]), ("b", vec!["indie"])] {
+            let artist = Artist {
+                name: id.to_uppercase(),
+                genres: genres.iter().map(|genre| genre.to_string()).collect(),
+            };
+            metadata.artists.insert(id.to_string(), artist);
+        }
+        metadata.features.insert("x".to_string(), Features { energy: 0.5, tempo: 100.0, ..Default::default() });
+        metadata.features.insert("y".to_string(), Features { energy: 1.0, tempo: 130.0, ..Default::default() });
+
+        // 2021-03-01 was a Monday.
+        let plays = vec![
+            play(1, 8, "x", "a", 239_000),
+            play(1, 9, "y", "b", 150_500),
+            play(2, 8, "x", "a", 239_000),
+            play(7, 23, "z", "a", 59_000),
+        ];
+        let stats = compute(&plays, &metadata, &Utc, 2);
+
+        // 3:59 + 2:30.5 + 3:59 + 0:59, rounded once.
+        assert_eq!((stats.plays, minutes(stats.duration_ms)), (4, 11));
+        assert_eq!(minutes(stats.tracks[0].duration_ms), 8);
+        let names = |tallies: &[Tally]| -> Vec<(String, usize)> {
+            tallies.iter().map(|tally| (tally.name.clone(), tally.plays)).collect()
+        };
+        assert_eq!(names(&stats.tracks), vec![("x".to_string(), 2), ("y".to_string(), 1)]);
+        assert_eq!(names(&stats.artists), vec![("A".to_string(), 3), ("B".to_string(), 1)]);
+        assert_eq!(names(&stats.genres), vec![("indie".to_string(), 4), ("folk".to_string(), 3)]);
+        assert_eq!((stats.heatmap[0][8], stats.heatmap[1][8], stats.heatmap[6][23]), (1, 1, 1));
+
+        let features = stats.features.unwrap();
+        assert_eq!(features.plays, 3);
+        assert!((features.tempo - 110.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,370 @@
+//! Token storage backends
+//!
+//! `AuthTokens` can live in the plaintext config file (the default), in the
+//! freedesktop Secret Service or in a passphrase protected file. The backend is
+//! picked with `token_storage` in the config file.
+use std::{
+    cell::RefCell,
+    error::Error,
+    fs,
+    io::Write,
+    num::NonZeroU32,
+    path::PathBuf,
+    process::{Command, Stdio},
+};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{
+    config_dir, load_config, save_auth, AuthTokens, Config, TokenStorage, DEFAULT_PROFILE,
+};
+
+const SECRET_TOOL: &str = "secret-tool";
+const SECRET_TOOL_ENV: &str = "SPOTR_SECRET_TOOL";
+const PASSPHRASE_ENV: &str = "SPOTR_PASSPHRASE";
+const ENCRYPTED_FILE_STEM: &str = "tokens";
+
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+
+pub trait TokenStore {
+    /// Read the stored tokens, returning empty tokens if nothing is stored.
+    fn load(&self) -> Result<AuthTokens, Box<dyn Error>>;
+    fn save(&self, tokens: &AuthTokens) -> Result<(), Box<dyn Error>>;
+    fn clear(&self) -> Result<(), Box<dyn Error>>;
+}
+
+/// Open the token store selected in `config`.
+pub fn open(config: &Config) -> Result<Box<dyn TokenStore>, Box<dyn Error>> {
+    Ok(match config.token_storage {
+        TokenStorage::Plaintext => Box::new(PlaintextStore::new(&config.profile)),
+        TokenStorage::SecretService => Box::new(SecretServiceStore::new(&config.profile)),
+        TokenStorage::EncryptedFile => {
+            let dir = config_dir().ok_or("Cannot find the config directory.")?;
+            // The default profile keeps the name used before profiles existed.
+            let name = if config.profile == DEFAULT_PROFILE {
+                format!("{}.enc", ENCRYPTED_FILE_STEM)
+            } else {
+                format!("{}-{}.enc", ENCRYPTED_FILE_STEM, config.profile)
+            };
+            Box::new(EncryptedFileStore::new(dir.join(name)))
+        }
+    })
+}
+
+/// Load tokens from `store`, opened for `config`, first moving any tokens
+/// still left in the plaintext config file into it.
+pub fn load_tokens(config: &Config, store: &dyn TokenStore) -> Result<AuthTokens, Box<dyn Error>> {
+    if config.token_storage != TokenStorage::Plaintext && !config.auth.is_empty() {
+        migrate(config, store)?;
+    }
+
+    store.load()
+}
+
+/// Move plaintext tokens from the config file into `store`.
+fn migrate(config: &Config, store: &dyn TokenStore) -> Result<(), Box<dyn Error>> {
+    if store.load()?.is_empty() {
+        store.save(&config.auth)?;
+    }
+
+    save_auth(&config.profile, AuthTokens::default())
+}
+
+/// Tokens stored in the profile's section of the config file.
+pub struct PlaintextStore {
+    profile: String,
+}
+
+impl PlaintextStore {
+    pub fn new(profile: &str) -> Self {
+        PlaintextStore {
+            profile: profile.to_string(),
+        }
+    }
+}
+
+impl TokenStore for PlaintextStore {
+    fn load(&self) -> Result<AuthTokens, Box<dyn Error>> {
+        Ok(load_config(Some(&self.profile))?.auth)
+    }
+
+    fn save(&self, tokens: &AuthTokens) -> Result<(), Box<dyn Error>> {
+        save_auth(&self.profile, tokens.clone())
+    }
+
+    fn clear(&self) -> Result<(), Box<dyn Error>> {
+        self.save(&AuthTokens::default())
+    }
+}
+
+/// Tokens stored in the freedesktop Secret Service using libsecret's
+/// `secret-tool`. Set `SPOTR_SECRET_TOOL` to use a different binary.
+pub struct SecretServiceStore {
+    profile: String,
+    tool: String,
+}
+
+impl SecretServiceStore {
+    pub fn new(profile: &str) -> Self {
+        let tool = std::env::var(SECRET_TOOL_ENV).unwrap_or_else(|_| SECRET_TOOL.to_string());
+        SecretServiceStore::with_tool(profile, &tool)
+    }
+
+    /// Use `tool` in place of `secret-tool`, it has to take the same arguments.
+    pub fn with_tool(profile: &str, tool: &str) -> Self {
+        SecretServiceStore {
+            profile: profile.to_string(),
+            tool: tool.to_string(),
+        }
+    }
+
+    fn command(&self, action: &str) -> Command {
+        let mut cmd = Command::new(&self.tool);
+        cmd.arg(action);
+        if action == "store" {
+            cmd.arg(format!("--label=SpotR tokens ({})", self.profile));
+        }
+        cmd.args(["application", "spotr", "profile", &self.profile]);
+        cmd
+    }
+}
+
+impl TokenStore for SecretServiceStore {
+    fn load(&self) -> Result<AuthTokens, Box<dyn Error>> {
+        let output = self
+            .command("lookup")
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(|why| format!("Failed to run {}: {}", self.tool, why))?;
+
+        // `secret-tool lookup` exits with 1 when nothing is stored.
+        if !output.status.success() || output.stdout.is_empty() {
+            return Ok(AuthTokens::default());
+        }
+
+        Ok(serde_json::from_slice(&output.stdout)?)
+    }
+
+    fn save(&self, tokens: &AuthTokens) -> Result<(), Box<dyn Error>> {
+        let mut child = self
+            .command("store")
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|why| format!("Failed to run {}: {}", self.tool, why))?;
+
+        if let Some(stdin) = child.stdin.as_mut() {
+            stdin.write_all(&serde_json::to_vec(tokens)?)?;
+        }
+
+        if child.wait()?.success() {
+            Ok(())
+        } else {
+            Err(String::from("Failed to store tokens in the Secret Service.").into())
+        }
+    }
+
+    fn clear(&self) -> Result<(), Box<dyn Error>> {
+        self.command("clear").status()?;
+        Ok(())
+    }
+}
+
+/// Tokens stored in a file encrypted with ChaCha20-Poly1305, using a key
+/// derived from a passphrase with PBKDF2. The passphrase is read from
+/// `SPOTR_PASSPHRASE` or prompted for once per run.
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    passphrase: RefCell<Option<String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EncryptedFile {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedFileStore {
+    pub fn new(path: PathBuf) -> Self {
+        EncryptedFileStore {
+            path,
+            passphrase: RefCell::new(std::env::var(PASSPHRASE_ENV).ok()),
+        }
+    }
+
+    pub fn with_passphrase(path: PathBuf, passphrase: &str) -> Self {
+        EncryptedFileStore {
+            path,
+            passphrase: RefCell::new(Some(passphrase.to_string())),
+        }
+    }
+
+    fn passphrase(&self) -> Result<String, Box<dyn Error>> {
+        if let Some(passphrase) = self.passphrase.borrow().as_ref() {
+            return Ok(passphrase.clone());
+        }
+
+        let passphrase = rpassword::prompt_password_stderr("SpotR token passphrase: ")?;
+        *self.passphrase.borrow_mut() = Some(passphrase.clone());
+        Ok(passphrase)
+    }
+
+    fn key(&self, salt: &[u8]) -> Result<LessSafeKey, Box<dyn Error>> {
+        let mut key = [0; 32];
+        pbkdf2::derive(
+            pbkdf2::PBKDF2_HMAC_SHA256,
+            NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+            salt,
+            self.passphrase()?.as_bytes(),
+            &mut key,
+        );
+
+        let key = UnboundKey::new(&CHACHA20_POLY1305, &key).map_err(|_| "Invalid key length.")?;
+        Ok(LessSafeKey::new(key))
+    }
+}
+
+impl TokenStore for EncryptedFileStore {
+    fn load(&self) -> Result<AuthTokens, Box<dyn Error>> {
+        if !self.path.exists() {
+            return Ok(AuthTokens::default());
+        }
+
+        let file: EncryptedFile = serde_json::from_str(&fs::read_to_string(&self.path)?)?;
+        let salt = base64::decode(&file.salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(&base64::decode(&file.nonce)?)
+            .map_err(|_| "Corrupt token file.")?;
+        let mut data = base64::decode(&file.ciphertext)?;
+
+        let plaintext = self
+            .key(&salt)?
+            .open_in_place(nonce, Aad::empty(), &mut data)
+            .map_err(|_| "Wrong passphrase or corrupt token file.")?;
+
+        Ok(serde_json::from_slice(plaintext)?)
+    }
+
+    fn save(&self, tokens: &AuthTokens) -> Result<(), Box<dyn Error>> {
+        let rng = SystemRandom::new();
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        rng.fill(&mut salt).map_err(|_| "Failed to generate salt.")?;
+        rng.fill(&mut nonce).map_err(|_| "Failed to generate nonce.")?;
+
+        let mut data = serde_json::to_vec(tokens)?;
+        self.key(&salt)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut data,
+            )
+            .map_err(|_| "Failed to encrypt tokens.")?;
+
+        let file = EncryptedFile {
+            salt: base64::encode(salt),
+            nonce: base64::encode(nonce),
+            ciphertext: base64::encode(data),
+        };
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string(&file)?)?;
+
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<(), Box<dyn Error>> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+/// An empty directory for the test `name`, unique to this process so runs
+/// can't trip over each other's files.
+#[cfg(test)]
+pub(crate) fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("spotr-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> AuthTokens {
+        AuthTokens {
+            refresh_token: Some("refresh".to_string()),
+            access_token: Some("access".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_encrypted_file_roundtrip() {
+        let dir = test_dir("roundtrip");
+        let path = dir.join("tokens.enc");
+        let store = EncryptedFileStore::with_passphrase(path.clone(), "hunter2");
+
+        store.save(&tokens()).unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("refresh"));
+        assert_eq!(store.load().unwrap(), tokens());
+
+        store.clear().unwrap();
+        assert!(store.load().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_secret_service_mock() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Keeps the secret in a file next to the script, like secret-tool
+        // would in the keyring.
+        let dir = test_dir("secret-tool");
+        let tool = dir.join("secret-tool");
+        let secret = dir.join("secret");
+        let script = format!(
+            "#!/bin/sh\n\
+             case $1 in\n\
+             store) cat > {0} ;;\n\
+             lookup) cat {0} 2>/dev/null || exit 1 ;;\n\
+             clear) rm -f {0} ;;\n\
+             esac\n",
+            secret.display()
+        );
+        fs::write(&tool, script).unwrap();
+        fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let store = SecretServiceStore::with_tool("work", tool.to_str().unwrap());
+        assert!(store.load().unwrap().is_empty());
+        store.save(&tokens()).unwrap();
+        assert_eq!(store.load().unwrap(), tokens());
+        store.clear().unwrap();
+        assert!(store.load().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_file_wrong_passphrase() {
+        let dir = test_dir("passphrase");
+        let path = dir.join("tokens.enc");
+        EncryptedFileStore::with_passphrase(path.clone(), "hunter2")
+            .save(&tokens())
+            .unwrap();
+
+        let store = EncryptedFileStore::with_passphrase(path, "hunter3");
+        assert!(store.load().is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
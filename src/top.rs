@@ -0,0 +1,130 @@
+//! Saved rankings of top tracks and artists
+//!
+//! Spotify only serves the current ranking, so `sp top diff` keeps the last
+//! one it saw in `<data dir>/top/<profile>/<kind>_<range>.json` to compare
+//! against.
+use std::{collections::HashMap, error::Error, fs, io::ErrorKind, path::PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::data_dir;
+
+/// Top items at one point in time, best first.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ranking {
+    pub taken_at: DateTime<Utc>,
+    pub items: Vec<Ranked>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ranked {
+    pub uri: String,
+    pub name: String,
+    /// Artists of a track, genres of an artist.
+    pub detail: String,
+}
+
+/// How an item's rank changed. Ranks start at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RankChange<'a> {
+    Climbed { item: &'a Ranked, from: usize, to: usize },
+    Fell { item: &'a Ranked, from: usize, to: usize },
+    New { item: &'a Ranked, rank: usize },
+    Dropped { item: &'a Ranked, rank: usize },
+}
+
+fn path(profile: &str, name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = data_dir().ok_or("Cannot find the data directory.")?;
+    Ok(dir.join("top").join(profile).join(format!("{}.json", name)))
+}
+
+/// The ranking saved as `name` for `profile`, if any.
+pub fn load(profile: &str, name: &str) -> Result<Option<Ranking>, Box<dyn Error>> {
+    let path = path(profile, name)?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+        Err(why) if why.kind() == ErrorKind::NotFound => Ok(None),
+        Err(why) => Err(format!("Cannot read {}: {}", path.display(), why).into()),
+    }
+}
+
+/// Save `ranking` as `name` for `profile`, replacing the previous one.
+pub fn save(profile: &str, name: &str, ranking: &Ranking) -> Result<(), Box<dyn Error>> {
+    let path = path(profile, name)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(ranking)?)?;
+    Ok(())
+}
+
+/// Changes from `from` to `to`: climbers, fallers and new entries in the
+/// order of `to`, then items that dropped out in the order of `from`. Only
+/// the ranks both cover are compared, so rankings taken with different limits
+/// line up.
+pub fn diff<'a>(from: &'a [Ranked], to: &'a [Ranked]) -> Vec<RankChange<'a>> {
+    let common = from.len().min(to.len());
+    let (from, to) = (&from[..common], &to[..common]);
+    let rank_of = |items: &[Ranked]| -> HashMap<String, usize> {
+        items.iter().enumerate().map(|(i, item)| (item.uri.clone(), i + 1)).collect()
+    };
+    let (before, after) = (rank_of(from), rank_of(to));
+
+    let mut changes: Vec<RankChange> = to
+        .iter()
+        .enumerate()
+        .filter_map(|(i, item)| {
+            let rank = i + 1;
+            match before.get(&item.uri) {
+                None => Some(RankChange::New { item, rank }),
+                Some(&from) if from > rank => Some(RankChange::Climbed { item, from, to: rank }),
+                Some(&from) if from < rank => Some(RankChange::Fell { item, from, to: rank }),
+                Some(_) => None,
+            }
+        })
+        .collect();
+    changes.extend(
+        from.iter()
+            .enumerate()
+            .filter(|(_, item)| !after.contains_key(&item.uri))
+            .map(|(i, item)| RankChange::Dropped { item, rank: i + 1 }),
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(uris: &[&str]) -> Vec<Ranked> {
+        uris.iter()
+            .map(|uri| Ranked {
+                uri: uri.to_string(),
+                name: uri.to_string(),
+                detail: String::new(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let from = items(&["a", "b", "c", "d"]);
+        let to = items(&["c", "a", "e", "d"]);
+        let changes: Vec<String> = diff(&from, &to)
+            .iter()
+            .map(|change| match change {
+                RankChange::Climbed { item, from, to } => format!("{} {}>{}", item.uri, from, to),
+                RankChange::Fell { item, from, to } => format!("{} {}<{}", item.uri, from, to),
+                RankChange::New { item, rank } => format!("{} new {}", item.uri, rank),
+                RankChange::Dropped { item, rank } => format!("{} out {}", item.uri, rank),
+            })
+            .collect();
+        assert_eq!(changes, vec!["c 3>1", "a 1<2", "e new 3", "b out 2"]);
+
+        // A longer or shorter ranking is cut to the ranks both have.
+        let longer = items(&["a", "b", "c", "d", "e", "f"]);
+        assert!(diff(&from, &longer).is_empty());
+        assert!(diff(&longer, &from).is_empty());
+    }
+}